@@ -1,27 +1,32 @@
 use toy_xcb::{Event, Result, Window};
 
 fn main() -> Result<()> {
-    let w = Window::new(640, 480, "Example".to_string())?;
+    let mut w = Window::new(640, 480, "Example")?;
+    w.set_media_key_events(true);
 
-    'mainloop: loop {
-        match w.wait_event()? {
-            Event::MousePress(pos, _, _) => {
-                println!("clicked window: {:?}", pos);
+    for ev in w.events() {
+        match ev? {
+            Event::MousePress { pos, button, .. } => {
+                println!("clicked window: {:?} with {:?}", pos, button);
             }
             Event::Resize(size) => {
                 println!("resized window: {:?}", size);
             }
-            Event::KeyPress(sym, code, text) => {
+            Event::KeyPress(sym, code, text, _) => {
                 println!(
                     "key typed: sym={:?}, code={:?}, text=\"{}\"",
                     sym, code, text
                 );
             }
+            Event::MediaKey(sym) => {
+                println!("media key: {:?}", sym);
+            }
             Event::Close => {
                 println!("user close request");
-                break 'mainloop Ok(());
             }
             _ => {}
         }
     }
+
+    Ok(())
 }
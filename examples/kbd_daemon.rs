@@ -0,0 +1,48 @@
+//! Prints the active keyboard layout each time it changes,
+//! e.g. when a keyboard is plugged or the layout is switched.
+
+use toy_xcb::{Event, Result, WindowBuilder};
+
+use std::cell::Cell;
+use std::rc::Rc;
+use std::time::Duration;
+
+fn main() -> Result<()> {
+    let mut w = WindowBuilder::new(1, 1)
+        .title("Keyboard daemon")
+        .class("kbd_daemon", "KbdDaemon")
+        .build()?;
+    w.set_skip_taskbar(true)?;
+    w.set_skip_pager(true)?;
+
+    // xkb events are consumed by the window and never reach the event stream,
+    // so they are spotted with the event filter
+    let kbd_changed = Rc::new(Cell::new(false));
+    let changed = kbd_changed.clone();
+    w.set_event_filter(Box::new(move |ev| {
+        if let xcb::Event::Xkb(_) = ev {
+            changed.set(true);
+        }
+        true
+    }));
+
+    let mut layout = w.keyboard_layout();
+    println!("layout: {}", layout);
+
+    while w.is_connected() {
+        // the filter runs before the keyboard state is updated,
+        // so the layout is checked once the event is processed
+        if let Some(Event::Close) = w.wait_event_timeout(Duration::from_millis(100))? {
+            break;
+        }
+        if kbd_changed.replace(false) {
+            let new_layout = w.keyboard_layout();
+            if new_layout != layout {
+                println!("layout: {}", new_layout);
+                layout = new_layout;
+            }
+        }
+    }
+
+    Ok(())
+}
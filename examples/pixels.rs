@@ -0,0 +1,59 @@
+//! Renders an animated gradient on the CPU and presents it each frame.
+//! Resizing the window to a large size exercises the upload of images
+//! bigger than the maximum request length.
+
+use toy_xcb::geometry::ISize;
+use toy_xcb::{Event, Result, Window};
+
+use std::time::{Duration, Instant};
+
+const FRAME: Duration = Duration::from_millis(16);
+
+fn render(buffer: &mut Vec<u32>, size: ISize, t: f32) {
+    let (w, h) = (size.w as usize, size.h as usize);
+    buffer.resize(w * h, 0);
+    for y in 0..h {
+        for x in 0..w {
+            let fx = x as f32 / w as f32;
+            let fy = y as f32 / h as f32;
+            let r = (fx * 255.0) as u32;
+            let g = (fy * 255.0) as u32;
+            let b = ((t.sin() * 0.5 + 0.5) * 255.0) as u32;
+            buffer[y * w + x] = 0xff00_0000 | r << 16 | g << 8 | b;
+        }
+    }
+}
+
+fn main() -> Result<()> {
+    let w = Window::new(640, 480, "Pixels")?;
+
+    let start = Instant::now();
+    let mut size = ISize::new(640, 480);
+    let mut buffer = Vec::new();
+    let mut next_frame = Instant::now();
+
+    loop {
+        let timeout = next_frame.saturating_duration_since(Instant::now());
+        match w.wait_event_timeout(timeout)? {
+            Some(Event::Resize(new_size)) => {
+                size = new_size;
+                next_frame = Instant::now();
+            }
+            Some(Event::Expose) => {
+                next_frame = Instant::now();
+            }
+            Some(Event::Close) => break,
+            Some(_) => {}
+            None => {}
+        }
+
+        let now = Instant::now();
+        if now >= next_frame {
+            render(&mut buffer, size, start.elapsed().as_secs_f32());
+            w.present(&buffer, size)?;
+            next_frame = now + FRAME;
+        }
+    }
+
+    Ok(())
+}
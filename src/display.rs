@@ -0,0 +1,227 @@
+// This file is part of toy_xcb and is released under the terms
+// of the MIT license. See included LICENSE.txt file.
+
+use super::geometry::ISize;
+use super::{Error, Result};
+
+use xcb::x;
+
+/// Description of one X screen (not a monitor: with Xinerama or RandR,
+/// a single screen can span several monitors).
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct ScreenInfo {
+    pub index: usize,
+    pub root: x::Window,
+    pub size: ISize,
+    pub root_depth: u8,
+}
+
+/// Description of the X server, e.g. for an "about" dialog.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ConnectionInfo {
+    pub default_screen: usize,
+    pub screen_count: usize,
+    pub vendor: String,
+    /// Version of the vendor's release, not of the protocol.
+    pub release_number: u32,
+    pub protocol_major: u16,
+    pub protocol_minor: u16,
+}
+
+/// Opcode and base numbers of an extension, as negotiated with the server.
+/// The events and errors of the extension are numbered from the bases.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct ExtensionBases {
+    pub major_opcode: u8,
+    pub first_event: u8,
+    pub first_error: u8,
+}
+
+/// Layout of the pixels of a depth in the `ZPixmap` images exchanged
+/// with the server, e.g. by `PutImage`.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct ImageFormat {
+    pub depth: u8,
+    pub bits_per_pixel: u8,
+    /// Scanlines are padded to a multiple of this number of bits.
+    pub scanline_pad: u8,
+    pub byte_order: x::ImageOrder,
+}
+
+impl ImageFormat {
+    pub(crate) fn from_setup(setup: &x::Setup, depth: u8) -> Option<ImageFormat> {
+        setup
+            .pixmap_formats()
+            .iter()
+            .find(|f| f.depth() == depth)
+            .map(|f| ImageFormat {
+                depth,
+                bits_per_pixel: f.bits_per_pixel(),
+                scanline_pad: f.scanline_pad(),
+                byte_order: setup.image_byte_order(),
+            })
+    }
+
+    /// Number of bytes of a scanline of `width` pixels, padding included.
+    pub fn stride(&self, width: usize) -> usize {
+        let pad = self.scanline_pad as usize;
+        (width * self.bits_per_pixel as usize).div_ceil(pad) * pad / 8
+    }
+
+    /// Appends a scanline of `0xAARRGGBB` pixels to `data`, padding included.
+    /// Only 24 and 32 bits per pixel are supported.
+    pub(crate) fn encode_row(&self, row: &[u32], data: &mut Vec<u8>) {
+        let start = data.len();
+        for px in row {
+            let bytes = match self.byte_order {
+                x::ImageOrder::LsbFirst => px.to_le_bytes(),
+                x::ImageOrder::MsbFirst => px.to_be_bytes(),
+            };
+            match (self.bits_per_pixel, self.byte_order) {
+                (32, _) => data.extend_from_slice(&bytes),
+                (24, x::ImageOrder::LsbFirst) => data.extend_from_slice(&bytes[..3]),
+                (24, x::ImageOrder::MsbFirst) => data.extend_from_slice(&bytes[1..]),
+                (bpp, _) => unreachable!("unsupported bits per pixel: {}", bpp),
+            }
+        }
+        data.resize(start + self.stride(row.len()), 0);
+    }
+}
+
+/// A connection to the X server.
+pub struct Display {
+    pub(crate) conn: xcb::Connection,
+    def_screen: i32,
+}
+
+impl Display {
+    /// Opens a plain XCB connection to the default display.
+    /// Useful to query the screens before choosing where to create a window.
+    pub fn open() -> Result<Display> {
+        let (conn, def_screen) = xcb::Connection::connect(None)?;
+        Ok(Display { conn, def_screen })
+    }
+
+    /// Connects to the display `name`, or to the default display
+    /// (`DISPLAY` environment variable) if `None`.
+    /// Xlib can only open the default display, so a plain XCB connection
+    /// is used for a named display whatever `use_xlib`.
+    pub(crate) fn connect(
+        name: Option<&str>,
+        use_xlib: bool,
+        mandatory: &[xcb::Extension],
+        optional: &[xcb::Extension],
+    ) -> Result<Display> {
+        let (conn, def_screen) = if let Some(name) = name {
+            xcb::Connection::connect_with_extensions(Some(name), mandatory, optional)
+                .map_err(|_| Error::CannotOpenDisplay(name.to_string()))?
+        } else if use_xlib {
+            let (conn, def_screen) =
+                xcb::Connection::connect_with_xlib_display_and_extensions(mandatory, optional)?;
+            conn.set_event_queue_owner(xcb::EventQueueOwner::Xcb);
+            (conn, def_screen)
+        } else {
+            xcb::Connection::connect_with_extensions(None, mandatory, optional)?
+        };
+        Ok(Display { conn, def_screen })
+    }
+
+    pub fn default_screen(&self) -> usize {
+        self.def_screen as usize
+    }
+
+    pub fn screen_count(&self) -> usize {
+        self.conn.get_setup().roots().count()
+    }
+
+    /// Iterates over all the screens of the display.
+    pub fn screens(&self) -> impl Iterator<Item = ScreenInfo> + '_ {
+        self.conn
+            .get_setup()
+            .roots()
+            .enumerate()
+            .map(|(index, screen)| ScreenInfo {
+                index,
+                root: screen.root(),
+                size: ISize::new(
+                    screen.width_in_pixels() as i32,
+                    screen.height_in_pixels() as i32,
+                ),
+                root_depth: screen.root_depth(),
+            })
+    }
+
+    /// Describes the server, from the setup data received on connection.
+    pub fn server_info(&self) -> ConnectionInfo {
+        let setup = self.conn.get_setup();
+        ConnectionInfo {
+            default_screen: self.default_screen(),
+            screen_count: self.screen_count(),
+            vendor: setup.vendor().to_utf8().into_owned(),
+            release_number: setup.release_number(),
+            protocol_major: setup.protocol_major_version(),
+            protocol_minor: setup.protocol_minor_version(),
+        }
+    }
+
+    /// Returns the format of the images of `depth`, or `None` if the
+    /// server doesn't support this depth.
+    pub fn image_format(&self, depth: u8) -> Option<ImageFormat> {
+        ImageFormat::from_setup(self.conn.get_setup(), depth)
+    }
+
+    /// Returns the bases of `ext`, or `None` if the extension is not active
+    /// on the connection.
+    pub fn extension_bases(&self, ext: xcb::Extension) -> Option<ExtensionBases> {
+        self.conn
+            .active_extensions_data()
+            .iter()
+            .find(|data| data.ext == ext)
+            .map(|data| ExtensionBases {
+                major_opcode: data.major_opcode,
+                first_event: data.first_event,
+                first_error: data.first_error,
+            })
+    }
+}
+
+#[test]
+fn invalid_display_name() {
+    assert!(matches!(
+        Display::connect(Some("no colon"), true, &[], &[]),
+        Err(Error::CannotOpenDisplay(name)) if name == "no colon"
+    ));
+}
+
+#[test]
+fn image_rows() {
+    let mut format = ImageFormat {
+        depth: 24,
+        bits_per_pixel: 32,
+        scanline_pad: 32,
+        byte_order: x::ImageOrder::LsbFirst,
+    };
+    let row = [0x00_11_22_33, 0x00_44_55_66];
+    let mut data = Vec::new();
+    format.encode_row(&row, &mut data);
+    assert_eq!(vec![0x33, 0x22, 0x11, 0, 0x66, 0x55, 0x44, 0], data);
+
+    format.byte_order = x::ImageOrder::MsbFirst;
+    data.clear();
+    format.encode_row(&row, &mut data);
+    assert_eq!(vec![0, 0x11, 0x22, 0x33, 0, 0x44, 0x55, 0x66], data);
+
+    // packed pixels, the row is padded to 32 bits
+    format.bits_per_pixel = 24;
+    assert_eq!(8, format.stride(2));
+    data.clear();
+    format.encode_row(&row, &mut data);
+    assert_eq!(vec![0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0, 0], data);
+
+    format.byte_order = x::ImageOrder::LsbFirst;
+    format.scanline_pad = 8;
+    assert_eq!(6, format.stride(2));
+    data.clear();
+    format.encode_row(&row, &mut data);
+    assert_eq!(vec![0x33, 0x22, 0x11, 0x66, 0x55, 0x44], data);
+}
@@ -0,0 +1,94 @@
+// This file is part of toy_xcb and is released under the terms
+// of the MIT license. See included LICENSE.txt file.
+
+use xcb::{x, Xid};
+
+use std::ffi::OsString;
+use std::os::unix::ffi::OsStringExt;
+use std::path::PathBuf;
+
+/// Version of the XDND protocol advertised in `XdndAware`.
+pub(crate) const XDND_VERSION: u32 = 5;
+
+/// A drag in progress over the window, started by `XdndEnter`.
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct Drag {
+    pub source: x::Window,
+    pub version: u32,
+    /// Whether the source offers `text/uri-list`.
+    pub accept: bool,
+}
+
+impl Drag {
+    /// Whether an XDND message whose first data item is `source` belongs
+    /// to this drag. Messages from other windows must be ignored.
+    pub fn is_from(&self, source: u32) -> bool {
+        self.source.resource_id() == source
+    }
+}
+
+/// Extracts the local file paths of a `text/uri-list`.
+/// Comments and URIs other than `file:` are skipped.
+pub(crate) fn parse_uri_list(data: &[u8]) -> Vec<PathBuf> {
+    data.split(|&b| b == b'\n')
+        .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+        .filter(|line| !line.is_empty() && line[0] != b'#')
+        .filter_map(|uri| uri.strip_prefix(b"file://"))
+        .filter_map(|rest| {
+            // skip the host part, usually empty or the local hostname
+            let path = &rest[rest.iter().position(|&b| b == b'/')?..];
+            Some(PathBuf::from(OsString::from_vec(percent_decode(path))))
+        })
+        .collect()
+}
+
+fn percent_decode(s: &[u8]) -> Vec<u8> {
+    fn hex(b: u8) -> Option<u8> {
+        (b as char).to_digit(16).map(|d| d as u8)
+    }
+
+    let mut res = Vec::with_capacity(s.len());
+    let mut i = 0;
+    while i < s.len() {
+        if s[i] == b'%' && i + 2 < s.len() {
+            if let (Some(h), Some(l)) = (hex(s[i + 1]), hex(s[i + 2])) {
+                res.push(h << 4 | l);
+                i += 3;
+                continue;
+            }
+        }
+        res.push(s[i]);
+        i += 1;
+    }
+    res
+}
+
+#[test]
+fn uri_list() {
+    let data = b"# dropped from a file manager\r\n\
+                 file:///home/user/My%20Doc.txt\r\n\
+                 file://host/tmp/a%2\r\n\
+                 http://example.com/x\r\n\
+                 file:///tmp/caf%C3%A9\r\n";
+    assert_eq!(
+        vec![
+            PathBuf::from("/home/user/My Doc.txt"),
+            PathBuf::from("/tmp/a%2"),
+            PathBuf::from("/tmp/café"),
+        ],
+        parse_uri_list(data)
+    );
+}
+
+#[test]
+fn drag_source() {
+    use xcb::XidNew;
+
+    let drag = Drag {
+        source: x::Window::new(0x0060_0001),
+        version: XDND_VERSION,
+        accept: true,
+    };
+    assert!(drag.is_from(0x0060_0001));
+    assert!(!drag.is_from(0x0070_0001));
+}
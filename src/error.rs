@@ -3,17 +3,61 @@ pub type Result<T> = std::result::Result<T, Error>;
 #[derive(Debug)]
 pub enum Error {
     Xcb(xcb::Error),
+    Io(std::io::Error),
+    /// A window argument was `x::Window::none()` where a valid window is required.
+    InvalidWindow,
+    /// The requested screen index is not lower than the number of screens.
+    InvalidScreen(usize),
+    /// A property exists with another type or format than the requested one.
+    PropertyMismatch,
+    /// A feature is not supported by the X server.
+    Unsupported(&'static str),
+    /// The connection to the X server is broken, e.g. because the server exited.
+    /// It cannot be used anymore.
+    ConnectionClosed,
+    /// The named display could not be opened: the name is malformed,
+    /// or no server accepted the connection.
+    CannotOpenDisplay(String),
+    /// A grab was refused, e.g. because another client holds it.
+    GrabFailed(xcb::x::GrabStatus),
+    /// An argument is invalid, e.g. a size out of range or a buffer
+    /// too small for it.
+    InvalidArgument(&'static str),
 }
 
 impl From<xcb::Error> for Error {
     fn from(err: xcb::Error) -> Error {
-        Error::Xcb(err)
+        match err {
+            xcb::Error::Connection(err) if is_closed(&err) => Error::ConnectionClosed,
+            err => Error::Xcb(err),
+        }
+    }
+}
+
+/// Whether `err` leaves the connection shut down: all the connection
+/// errors of libxcb do, only the Xlib ones are raised before connecting.
+fn is_closed(err: &xcb::ConnError) -> bool {
+    matches!(
+        err,
+        xcb::ConnError::Connection
+            | xcb::ConnError::ClosedExtNotSupported
+            | xcb::ConnError::ClosedMemInsufficient
+            | xcb::ConnError::ClosedReqLenExceed
+            | xcb::ConnError::ClosedParseErr
+            | xcb::ConnError::ClosedInvalidScreen
+            | xcb::ConnError::ClosedFdPassingFailed
+    )
+}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Error {
+        Error::Io(err)
     }
 }
 
 impl From<xcb::ConnError> for Error {
     fn from(err: xcb::ConnError) -> Error {
-        Error::Xcb(err.into())
+        xcb::Error::from(err).into()
     }
 }
 
@@ -22,3 +66,25 @@ impl From<xcb::ProtocolError> for Error {
         Error::Xcb(err.into())
     }
 }
+
+#[test]
+fn connection_closed() {
+    assert!(matches!(
+        Error::from(xcb::ConnError::Connection),
+        Error::ConnectionClosed
+    ));
+    for err in [
+        xcb::ConnError::ClosedExtNotSupported,
+        xcb::ConnError::ClosedMemInsufficient,
+        xcb::ConnError::ClosedReqLenExceed,
+        xcb::ConnError::ClosedParseErr,
+        xcb::ConnError::ClosedInvalidScreen,
+        xcb::ConnError::ClosedFdPassingFailed,
+    ] {
+        assert!(matches!(Error::from(err), Error::ConnectionClosed));
+    }
+    assert!(matches!(
+        Error::from(xcb::ConnError::XOpenDisplay),
+        Error::Xcb(xcb::Error::Connection(xcb::ConnError::XOpenDisplay))
+    ));
+}
@@ -1,26 +1,87 @@
 // This file is part of toy_xcb and is released under the terms
 // of the MIT license. See included LICENSE.txt file.
 
-use super::geometry::{IPoint, ISize};
+use super::geometry::{FPoint, IPoint, IRect, ISize};
 use super::{key, mouse, window};
 
-#[derive(Debug)]
+use std::path::PathBuf;
+
+#[derive(Clone, PartialEq, Debug)]
 pub enum Event {
     Show,
     Hide,
+    /// Part of the window was exposed and must be redrawn. Only reports
+    /// `Expose` events of the server: the `GraphicsExposure` events of a
+    /// copy are not, as `Window::present` never causes them.
     Expose,
     Close,
+    /// Emitted instead of `Resize` and `Expose` when redraw coalescing is
+    /// enabled (see `Window::set_coalesce_redraws`).
+    /// Holds the full bounds of the window at its latest size.
+    Redraw(IRect),
 
     Resize(ISize),
+    /// The window moved, to this position in root coordinates.
+    /// When the window is also resized, `Resize` comes first.
     Move(IPoint),
+    /// The window moved or was resized, to this geometry in root
+    /// coordinates. Replaces `Resize` and `Move` once enabled with
+    /// `Window::set_configure_events`.
+    Configure(IRect),
     StateChange(window::State),
     Enter(IPoint),
     Leave(IPoint),
+    /// The screen configuration changed (monitor plugged, unplugged, rotated...).
+    /// `Window::monitors` gives the new monitor list.
+    MonitorsChanged,
+    /// A presentation of `Window::present_pixmap` reached the screen.
+    /// `ust` is the time it happened, in microseconds, and `msc` is the
+    /// count of vertical blanks of the monitor, which gives the frame
+    /// timing to pace the next presentation.
+    PresentComplete {
+        serial: u32,
+        ust: u64,
+        msc: u64,
+    },
+    /// The keyboard layout or the key mapping changed.
+    /// Key names and texts computed before are possibly stale.
+    KeymapChanged,
+    /// Files were dragged from another application and dropped on the window.
+    DropFiles(Vec<PathBuf>),
 
-    MousePress(IPoint, mouse::Buttons, key::Mods),
-    MouseRelease(IPoint, mouse::Buttons, key::Mods),
+    /// `button` is the one pressed. `state` holds the buttons held,
+    /// including the one pressed.
+    MousePress {
+        pos: IPoint,
+        button: mouse::Button,
+        state: mouse::Buttons,
+        mods: key::Mods,
+    },
+    /// `button` is the one released. `state` holds the buttons held,
+    /// including the one released.
+    MouseRelease {
+        pos: IPoint,
+        button: mouse::Button,
+        state: mouse::Buttons,
+        mods: key::Mods,
+    },
     MouseMove(IPoint, mouse::Buttons, key::Mods),
+    /// Unaccelerated motion of a pointer device, in device units.
+    /// Only emitted once enabled with `Window::set_raw_motion`.
+    RawMouseMove(FPoint),
 
-    KeyPress(key::Sym, key::Code, String),
-    KeyRelease(key::Sym, key::Code, String),
+    /// The text is what the key press produces with the current modifiers,
+    /// or an empty string for keys that produce no text.
+    /// The modifiers are those held after the press, so that
+    /// `key::Shortcut::matches` needs nothing else than the event.
+    KeyPress(key::Sym, key::Code, String, key::Mods),
+    /// The text is empty unless `Window::set_release_text` is enabled:
+    /// text is entered on press, and a release would otherwise look like
+    /// a second input of the same character.
+    /// The modifiers are those held after the release.
+    KeyRelease(key::Sym, key::Code, String, key::Mods),
+    /// A media key was pressed (see `key::Sym::is_media`).
+    /// Replaces the `KeyPress` and `KeyRelease` of media keys once enabled
+    /// with `Window::set_media_key_events`.
+    MediaKey(key::Sym),
 }
@@ -1,8 +1,16 @@
 // This file is part of toy_xcb and is released under the terms
 // of the MIT license. See included LICENSE.txt file.
 
-use std::cmp::Eq;
-use std::ops::{Add, Div, Mul, Sub};
+//! Geometry primitives.
+//!
+//! Float based types only implement `PartialEq`:
+//!
+//! ```compile_fail
+//! fn assert_eq<T: Eq>() {}
+//! assert_eq::<toy_xcb::geometry::FPoint>();
+//! ```
+
+use std::ops::{Add, Div, Mul, Neg, Sub};
 
 pub type FPoint = Point<f32>;
 pub type IPoint = Point<i32>;
@@ -16,7 +24,11 @@ pub type IRect = Rect<i32>;
 pub type FMargins = Margins<f32>;
 pub type IMargins = Margins<i32>;
 
-#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+// The derived `Eq`, `Ord` and `Hash` implementations are bound on the same
+// trait for `T`, so only the integer types get them.
+// `Point` and `Size` are ordered lexicographically (`x` then `y`, `w` then `h`).
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
 pub struct Point<T: Copy> {
     pub x: T,
     pub y: T,
@@ -28,7 +40,7 @@ impl<T: Copy> Point<T> {
     }
 }
 
-#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
 pub struct Size<T: Copy> {
     pub w: T,
     pub h: T,
@@ -40,7 +52,7 @@ impl<T: Copy> Size<T> {
     }
 }
 
-#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
 pub struct Rect<T: Copy> {
     pub x: T,
     pub y: T,
@@ -96,7 +108,149 @@ impl<T: Copy> Rect<T> {
     }
 }
 
-#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+impl<T> Rect<T>
+where
+    T: Copy + PartialOrd + Default + Add<Output = T> + Sub<Output = T> + Neg<Output = T>,
+{
+    /// Returns the same rectangle with non-negative width and height,
+    /// moving the origin accordingly.
+    /// E.g. a rectangle dragged up and left from its origin has negative size.
+    pub fn normalized(&self) -> Rect<T> {
+        let zero = T::default();
+        let mut r = *self;
+        if r.w < zero {
+            r.x = r.x + r.w;
+            r.w = -r.w;
+        }
+        if r.h < zero {
+            r.y = r.y + r.h;
+            r.h = -r.h;
+        }
+        r
+    }
+
+    /// Checks whether `p` is inside the rectangle.
+    /// The left and top edges are inside, the right and bottom edges are outside.
+    pub fn contains(&self, p: Point<T>) -> bool {
+        let r = self.normalized();
+        p.x >= r.x && p.x < r.x + r.w && p.y >= r.y && p.y < r.y + r.h
+    }
+
+    /// Returns the intersection of both rectangles, or `None` if they don't overlap.
+    pub fn intersection(&self, other: &Rect<T>) -> Option<Rect<T>> {
+        fn max<T: PartialOrd>(a: T, b: T) -> T {
+            if a > b {
+                a
+            } else {
+                b
+            }
+        }
+        fn min<T: PartialOrd>(a: T, b: T) -> T {
+            if a < b {
+                a
+            } else {
+                b
+            }
+        }
+
+        let a = self.normalized();
+        let b = other.normalized();
+        let x = max(a.x, b.x);
+        let y = max(a.y, b.y);
+        let right = min(a.x + a.w, b.x + b.w);
+        let bottom = min(a.y + a.h, b.y + b.h);
+        if right > x && bottom > y {
+            Some(Rect::new(x, y, right - x, bottom - y))
+        } else {
+            None
+        }
+    }
+}
+
+impl<T> Rect<T>
+where
+    T: Copy + Add<Output = T>,
+{
+    /// Returns the rectangle moved by `delta`.
+    pub fn translate(&self, delta: Point<T>) -> Rect<T> {
+        Rect::new_ps(self.point() + delta, self.size())
+    }
+}
+
+impl<T> Rect<T>
+where
+    T: Copy + PartialOrd + Default + From<u8>,
+    T: Add<Output = T> + Sub<Output = T> + Mul<Output = T> + Div<Output = T>,
+{
+    /// Returns the center of the rectangle.
+    /// With integer coordinates, the center is rounded towards the origin.
+    pub fn center(&self) -> Point<T> {
+        let two = T::from(2);
+        Point::new(self.x + self.w / two, self.y + self.h / two)
+    }
+
+    /// Returns the rectangle grown by `dx` on the left and right sides
+    /// and by `dy` on the top and bottom sides, keeping the same center.
+    /// Negative values shrink the rectangle. A rectangle shrunk by more
+    /// than its size gets a zero width or height, at its center.
+    pub fn inflate(&self, dx: T, dy: T) -> Rect<T> {
+        let zero = T::default();
+        let two = T::from(2);
+        let center = self.center();
+        let mut r = *self;
+        r.w = self.w + dx * two;
+        if r.w < zero {
+            r.x = center.x;
+            r.w = zero;
+        } else {
+            r.x = self.x - dx;
+        }
+        r.h = self.h + dy * two;
+        if r.h < zero {
+            r.y = center.y;
+            r.h = zero;
+        } else {
+            r.y = self.y - dy;
+        }
+        r
+    }
+}
+
+// Linear interpolation is computed as `a * (1 - t) + b * t` rather than
+// `a + (b - a) * t`, so that `t = 1` gives exactly `b`.
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a * (1.0 - t) + b * t
+}
+
+impl Point<f32> {
+    /// Linear interpolation from `self` (`t = 0`) to `other` (`t = 1`).
+    /// `t` is not clamped: values outside `[0, 1]` extrapolate.
+    pub fn lerp(self, other: FPoint, t: f32) -> FPoint {
+        Point::new(lerp(self.x, other.x, t), lerp(self.y, other.y, t))
+    }
+}
+
+impl Size<f32> {
+    /// Linear interpolation from `self` (`t = 0`) to `other` (`t = 1`).
+    /// `t` is not clamped: values outside `[0, 1]` extrapolate.
+    pub fn lerp(self, other: FSize, t: f32) -> FSize {
+        Size::new(lerp(self.w, other.w, t), lerp(self.h, other.h, t))
+    }
+}
+
+impl Rect<f32> {
+    /// Linear interpolation of position and size from `self` (`t = 0`)
+    /// to `other` (`t = 1`).
+    /// `t` is not clamped: values outside `[0, 1]` extrapolate.
+    pub fn lerp(self, other: FRect, t: f32) -> FRect {
+        Rect::new_ps(
+            self.point().lerp(other.point(), t),
+            self.size().lerp(other.size(), t),
+        )
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
 pub struct Margins<T: Copy> {
     pub l: T,
     pub r: T,
@@ -113,6 +267,14 @@ impl<T: Copy> Margins<T> {
             b: b,
         }
     }
+    pub fn uniform(v: T) -> Margins<T> {
+        Margins {
+            l: v,
+            r: v,
+            t: v,
+            b: v,
+        }
+    }
 }
 
 pub trait HasArea {
@@ -209,6 +371,90 @@ where
     }
 }
 
+impl<T> Add for Size<T>
+where
+    T: Add<Output = T> + Copy,
+{
+    type Output = Size<T>;
+
+    fn add(self, rhs: Size<T>) -> Size<T> {
+        Size {
+            w: self.w + rhs.w,
+            h: self.h + rhs.h,
+        }
+    }
+}
+
+impl<T> Sub for Size<T>
+where
+    T: Sub<Output = T> + Copy,
+{
+    type Output = Size<T>;
+
+    fn sub(self, rhs: Size<T>) -> Size<T> {
+        Size {
+            w: self.w - rhs.w,
+            h: self.h - rhs.h,
+        }
+    }
+}
+
+impl<T> Mul<T> for Size<T>
+where
+    T: Mul<Output = T> + Copy,
+{
+    type Output = Size<T>;
+
+    fn mul(self, rhs: T) -> Size<T> {
+        Size {
+            w: self.w * rhs,
+            h: self.h * rhs,
+        }
+    }
+}
+
+impl<T> Div<T> for Size<T>
+where
+    T: Div<Output = T> + Copy,
+{
+    type Output = Size<T>;
+
+    fn div(self, rhs: T) -> Size<T> {
+        Size {
+            w: self.w / rhs,
+            h: self.h / rhs,
+        }
+    }
+}
+
+impl<T> Add<Size<T>> for Point<T>
+where
+    T: Add<Output = T> + Copy,
+{
+    type Output = Point<T>;
+
+    fn add(self, rhs: Size<T>) -> Point<T> {
+        Point {
+            x: self.x + rhs.w,
+            y: self.y + rhs.h,
+        }
+    }
+}
+
+impl<T> Sub<Size<T>> for Point<T>
+where
+    T: Sub<Output = T> + Copy,
+{
+    type Output = Point<T>;
+
+    fn sub(self, rhs: Size<T>) -> Point<T> {
+        Point {
+            x: self.x - rhs.w,
+            y: self.y - rhs.h,
+        }
+    }
+}
+
 impl<T> Add<Margins<T>> for Rect<T>
 where
     T: Add<Output = T> + Sub<Output = T> + Copy,
@@ -261,9 +507,117 @@ fn ops() {
     //assert_eq!(Point::new(6, 8), 2 * v1);
     assert_eq!(Point::new(3, 1), v2 / 2);
 
+    let s1 = Size::new(5, 6);
+    let s2 = Size::new(2, 4);
+
+    assert_eq!(Size::new(7, 10), s1 + s2);
+    assert_eq!(Size::new(3, 2), s1 - s2);
+    assert_eq!(Size::new(10, 12), s1 * 2);
+    assert_eq!(Size::new(2, 3), s1 / 2);
+    assert_eq!(FSize::new(7.5, 9.0), FSize::new(5.0, 6.0) * 1.5);
+
+    assert_eq!(Point::new(8, 10), v1 + s1);
+    assert_eq!(Point::new(1, 0), v1 - s2);
+
     let r = Rect::new(5, 6, 7, 8);
     let m = Margins::new(2, 2, 2, 2);
 
     assert_eq!(Rect::new(3, 4, 11, 12), r + m);
     assert_eq!(Rect::new(7, 8, 3, 4), r - m);
 }
+
+#[test]
+fn margins() {
+    assert_eq!(Margins::new(3, 3, 3, 3), Margins::uniform(3));
+
+    let r = Rect::new(5, 6, 7, 8);
+    assert_eq!(Rect::new(4, 5, 9, 10), r + Margins::uniform(1));
+}
+
+#[test]
+fn hash_ord() {
+    use std::collections::HashMap;
+
+    let mut map = HashMap::new();
+    map.insert(Size::new(640, 480), "vga");
+    map.insert(Size::new(800, 600), "svga");
+    assert_eq!(Some(&"svga"), map.get(&Size::new(800, 600)));
+
+    let mut points = vec![Point::new(2, 1), Point::new(1, 5), Point::new(1, 2)];
+    points.sort();
+    assert_eq!(
+        vec![Point::new(1, 2), Point::new(1, 5), Point::new(2, 1)],
+        points
+    );
+}
+
+#[test]
+fn float_eq() {
+    fn assert_partial_eq<T: PartialEq>() {}
+    fn assert_eq<T: Eq>() {}
+
+    assert_partial_eq::<FPoint>();
+    assert_partial_eq::<FSize>();
+    assert_partial_eq::<FRect>();
+    assert_partial_eq::<FMargins>();
+    assert_eq::<IPoint>();
+    assert_eq::<ISize>();
+    assert_eq::<IRect>();
+    assert_eq::<IMargins>();
+}
+
+#[test]
+fn normalized() {
+    // dragged up and left from (10, 10) to (4, 6)
+    let r = Rect::new(10, 10, -6, -4);
+    let n = r.normalized();
+    assert_eq!(Rect::new(4, 6, 6, 4), n);
+    assert_eq!(24, n.area());
+    assert_eq!(n, n.normalized());
+
+    assert!(r.contains(Point::new(4, 6)));
+    assert!(r.contains(Point::new(9, 9)));
+    assert!(!r.contains(Point::new(10, 10)));
+    assert!(!r.contains(Point::new(3, 8)));
+
+    let other = Rect::new(8, 0, 10, 8);
+    assert_eq!(Some(Rect::new(8, 6, 2, 2)), r.intersection(&other));
+    assert_eq!(Some(Rect::new(8, 6, 2, 2)), other.intersection(&r));
+    assert_eq!(None, r.intersection(&Rect::new(10, 0, 5, 5)));
+}
+
+#[test]
+fn inflate() {
+    let r = Rect::new(10, 20, 30, 40);
+    assert_eq!(Point::new(25, 40), r.center());
+    assert_eq!(Rect::new(15, 25, 30, 40), r.translate(Point::new(5, 5)));
+
+    assert_eq!(Rect::new(8, 17, 34, 46), r.inflate(2, 3));
+    assert_eq!(Rect::new(12, 23, 26, 34), r.inflate(-2, -3));
+    assert_eq!(r.center(), r.inflate(-2, -3).center());
+
+    // shrunk more than its size
+    assert_eq!(Rect::new(25, 30, 0, 20), r.inflate(-20, -10));
+    assert_eq!(Rect::new(25, 40, 0, 0), r.inflate(-16, -21));
+
+    let f = FRect::new(0.0, 0.0, 3.0, 1.0);
+    assert_eq!(FPoint::new(1.5, 0.5), f.center());
+    assert_eq!(FRect::new(1.5, -1.0, 0.0, 3.0), f.inflate(-2.0, 1.0));
+}
+
+#[test]
+fn lerp_bounds() {
+    let a = FRect::new(0.1, 0.7, 100.3, 20.9);
+    let b = FRect::new(-3.3, 12.1, 0.7, 1e6);
+
+    assert_eq!(a, a.lerp(b, 0.0));
+    assert_eq!(b, a.lerp(b, 1.0));
+    assert_eq!(
+        FPoint::new(1.0, 3.0),
+        FPoint::new(0.0, 2.0).lerp(FPoint::new(2.0, 4.0), 0.5)
+    );
+    assert_eq!(
+        FSize::new(4.0, 6.0),
+        FSize::new(0.0, 2.0).lerp(FSize::new(2.0, 4.0), 2.0)
+    );
+}
@@ -6,24 +6,72 @@ use super::key;
 use super::Result;
 use xkbcommon::xkb;
 
-use xcb;
+use xcb::{self, x};
 
 use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
-use std::mem;
 
+/// Translates the key events of an X connection to `Event`s, with the
+/// keymap of the server, and tracks the modifiers held.
+///
+/// `Window` does it for its own events, but a keyboard can also be used
+/// with a connection managed by the application. The XKB events selected
+/// by `Keyboard::new` must then be passed back to the keyboard:
+///
+/// ```no_run
+/// use toy_xcb::Keyboard;
+/// use xcb::{x, xkb};
+///
+/// # fn main() -> toy_xcb::Result<()> {
+/// let (conn, _) = xcb::Connection::connect_with_extensions(None, &[], &[xcb::Extension::Xkb])?;
+/// let kbd = Keyboard::new(&conn)?;
+/// loop {
+///     match conn.wait_for_event()? {
+///         xcb::Event::X(x::Event::KeyPress(ev)) => println!("{:?}", kbd.make_key_event(&ev, true)),
+///         xcb::Event::Xkb(xkb::Event::StateNotify(ev)) => kbd.update_state(&ev),
+///         xcb::Event::Xkb(xkb::Event::MapNotify(_)) => kbd.reload_keymap(&conn),
+///         _ => {}
+///     }
+/// }
+/// # }
+/// ```
 pub struct Keyboard {
-    _context: xkb::Context,
-    _keymap: xkb::Keymap,
-    device_id: i32,
-    state: RefCell<xkb::State>,
+    mapping: RefCell<Mapping>,
     keysym_map: HashMap<u32, key::Sym>,
-    keycode_table: [key::Code; 256],
-    mods: Cell<u8>,
+    // Built from the XKB key names of the server keymap, which identify the
+    // physical keys whatever the layout. Keys with an unknown name fall back
+    // to the evdev keycodes of `build_keycode_table`.
+    keycode_table: RefCell<[key::Code; 256]>,
+    mods: Cell<key::Mods>,
+    release_text: Cell<bool>,
+}
+
+/// Translation of keycodes to keysyms and text.
+enum Mapping {
+    Xkb {
+        context: xkb::Context,
+        keymap: xkb::Keymap,
+        state: xkb::State,
+        device_id: i32,
+    },
+    /// Fallback for servers without the XKB extension: a single layout,
+    /// with only Shift, CapsLock and NumLock taken into account.
+    Core(CoreMapping),
 }
 
 impl Keyboard {
+    /// Fetches the keymap of the core keyboard of `connection`, and selects
+    /// the XKB events that report its changes.
+    /// XKB is only used if the extension is active on the connection,
+    /// otherwise the keyboard falls back on the core mapping.
     pub fn new(connection: &xcb::Connection) -> Result<Keyboard> {
+        if !connection
+            .active_extensions()
+            .any(|ext| ext == xcb::Extension::Xkb)
+        {
+            return Keyboard::new_core(connection);
+        }
+
         {
             let xkbver =
                 connection.wait_for_reply(connection.send_request(&xcb::xkb::UseExtension {
@@ -31,12 +79,15 @@ impl Keyboard {
                     wanted_minor: xkb::x11::MIN_MINOR_XKB_VERSION,
                 }))?;
 
-            assert!(
-                xkbver.supported(),
-                "required xcb-xkb-{}-{} is not supported",
-                xkb::x11::MIN_MAJOR_XKB_VERSION,
-                xkb::x11::MIN_MINOR_XKB_VERSION
-            );
+            if !xkbver.supported() {
+                #[cfg(feature = "log")]
+                log::warn!(
+                    "xcb-xkb-{}-{} is not supported, falling back to the core keyboard",
+                    xkb::x11::MIN_MAJOR_XKB_VERSION,
+                    xkb::x11::MIN_MINOR_XKB_VERSION
+                );
+                return Keyboard::new_core(connection);
+            }
         }
 
         let events = xcb::xkb::EventType::NEW_KEYBOARD_NOTIFY
@@ -52,8 +103,7 @@ impl Keyboard {
             | xcb::xkb::MapPart::VIRTUAL_MOD_MAP;
 
         connection.check_request(connection.send_request_checked(&xcb::xkb::SelectEvents {
-            device_spec: unsafe { mem::transmute::<_, u32>(xcb::xkb::Id::UseCoreKbd) }
-                as xcb::xkb::DeviceSpec,
+            device_spec: xcb::xkb::Id::UseCoreKbd as xcb::xkb::DeviceSpec,
             affect_which: events,
             clear: xcb::xkb::EventType::empty(),
             select_all: events,
@@ -63,99 +113,238 @@ impl Keyboard {
         }))?;
 
         let context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
-        let device_id = xkb::x11::get_core_keyboard_device_id(&connection);
+        let device_id = xkb::x11::get_core_keyboard_device_id(connection);
         let keymap = xkb::x11::keymap_new_from_device(
             &context,
-            &connection,
+            connection,
             device_id,
             xkb::KEYMAP_COMPILE_NO_FLAGS,
         );
-        let state = xkb::x11::state_new_from_device(&keymap, &connection, device_id);
+        let state = xkb::x11::state_new_from_device(&keymap, connection, device_id);
 
-        Ok(Keyboard {
-            _context: context,
-            _keymap: keymap,
-            device_id,
-            state: RefCell::new(state),
+        Ok(Keyboard::with_keymap(context, keymap, state, device_id))
+    }
+
+    fn new_core(connection: &xcb::Connection) -> Result<Keyboard> {
+        let mapping = CoreMapping::fetch(connection)?;
+        Ok(Keyboard::with_mapping(
+            Mapping::Core(mapping),
+            build_keycode_table(),
+        ))
+    }
+
+    fn with_keymap(
+        context: xkb::Context,
+        keymap: xkb::Keymap,
+        state: xkb::State,
+        device_id: i32,
+    ) -> Keyboard {
+        let keycode_table = build_keymap_keycode_table(&keymap);
+        Keyboard::with_mapping(
+            Mapping::Xkb {
+                context,
+                keymap,
+                state,
+                device_id,
+            },
+            keycode_table,
+        )
+    }
+
+    fn with_mapping(mapping: Mapping, keycode_table: [key::Code; 256]) -> Keyboard {
+        Keyboard {
+            mapping: RefCell::new(mapping),
             keysym_map: build_keysym_map(),
-            keycode_table: build_keycode_table(),
-            mods: Cell::new(0),
-        })
+            keycode_table: RefCell::new(keycode_table),
+            mods: Cell::new(key::Mods::default()),
+            release_text: Cell::new(false),
+        }
     }
 
-    pub fn make_key_event(&self, xcb_ev: &xcb::x::KeyPressEvent, press: bool) -> Event {
-        let xcode = xcb_ev.detail() as xkb::Keycode;
-        let xsym = self.state.borrow().key_get_one_sym(xcode);
+    /// Builds a keyboard without X server, from the evdev rules and
+    /// the given XKB layout (e.g. "us") and options, to test the event
+    /// translation. The keymap is compiled from the xkeyboard-config files.
+    #[cfg(test)]
+    pub(crate) fn headless(layout: &str, options: &str) -> Keyboard {
+        let context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
+        let keymap = xkb::Keymap::new_from_names(
+            &context,
+            "evdev",
+            "pc105",
+            layout,
+            "",
+            Some(options.to_string()),
+            xkb::KEYMAP_COMPILE_NO_FLAGS,
+        )
+        .unwrap_or_else(|| panic!("failed to compile the {} keymap", layout));
+        let state = xkb::State::new(&keymap);
+        Keyboard::with_keymap(context, keymap, state, -1)
+    }
 
-        let code = self.get_keycode(xcode);
-        let mut mod_mask: u8 = 0;
-        match code {
-            key::Code::LeftCtrl => {
-                mod_mask |= key::MODS_LEFT_CTRL;
-            }
-            key::Code::LeftShift => {
-                mod_mask |= key::MODS_LEFT_SHIFT;
-            }
-            key::Code::LeftAlt => {
-                mod_mask |= key::MODS_LEFT_ALT;
-            }
-            key::Code::LeftSuper => {
-                mod_mask |= key::MODS_LEFT_SUPER;
-            }
-            key::Code::RightCtrl => {
-                mod_mask |= key::MODS_RIGHT_CTRL;
+    /// Fetches the keymap of the server again, after it was changed.
+    pub fn reload_keymap(&self, connection: &xcb::Connection) {
+        let mut mapping = self.mapping.borrow_mut();
+        match &mut *mapping {
+            Mapping::Xkb {
+                context,
+                keymap,
+                state,
+                device_id,
+            } => {
+                *keymap = xkb::x11::keymap_new_from_device(
+                    context,
+                    connection,
+                    *device_id,
+                    xkb::KEYMAP_COMPILE_NO_FLAGS,
+                );
+                *state = xkb::x11::state_new_from_device(keymap, connection, *device_id);
+                *self.keycode_table.borrow_mut() = build_keymap_keycode_table(keymap);
             }
-            key::Code::RightShift => {
-                mod_mask |= key::MODS_RIGHT_SHIFT;
+            Mapping::Core(core) => {
+                // on failure, the previous mapping is kept, the error being
+                // reported by the next wait for events
+                if let Ok(fetched) = CoreMapping::fetch(connection) {
+                    *core = fetched;
+                }
             }
-            key::Code::RightAlt => {
-                mod_mask |= key::MODS_RIGHT_ALT;
-            }
-            key::Code::RightSuper => {
-                mod_mask |= key::MODS_RIGHT_SUPER;
-            }
-            _ => {}
         }
+    }
 
+    /// Translates a key press or release to `Event::KeyPress` or
+    /// `Event::KeyRelease`, and tracks the modifiers.
+    pub fn make_key_event(&self, xcb_ev: &xcb::x::KeyPressEvent, press: bool) -> Event {
+        self.key_event(xcb_ev.detail() as xkb::Keycode, xcb_ev.state(), press)
+    }
+
+    /// The `Sym` of the event is normalized to the uppercase letter for
+    /// a..z, so that it identifies the key whatever the Shift state,
+    /// whereas the text is the character actually typed.
+    /// `state` is the modifier state of the event, only used without XKB.
+    fn key_event(&self, xcode: xkb::Keycode, state: x::KeyButMask, press: bool) -> Event {
+        let (xsym, text) = match &*self.mapping.borrow() {
+            Mapping::Xkb { state, .. } => (state.key_get_one_sym(xcode), state.key_get_utf8(xcode)),
+            Mapping::Core(core) => {
+                let xsym = core.keysym(xcode, state);
+                (xsym, keysym_text(xsym))
+            }
+        };
+
+        let code = self.get_keycode(xcode);
+        let mod_mask = key_modifier(code, self.base_keysym(xcode));
         if mod_mask != 0 {
             let mut mods = self.mods.get();
-            if press {
-                mods |= mod_mask;
-            } else {
-                mods &= !mod_mask;
-            }
+            mods.set_held(mod_mask, press);
             self.mods.set(mods);
         }
 
         if press {
-            Event::KeyPress(
-                self.get_keysym(xsym),
-                code,
-                self.state.borrow().key_get_utf8(xcode),
-            )
+            Event::KeyPress(self.get_keysym(xsym), code, text, self.mods.get())
+        } else if self.release_text.get() {
+            Event::KeyRelease(self.get_keysym(xsym), code, text, self.mods.get())
         } else {
-            Event::KeyRelease(self.get_keysym(xsym), code, String::new())
+            Event::KeyRelease(self.get_keysym(xsym), code, String::new(), self.mods.get())
         }
     }
 
+    /// Forgets the held modifiers, e.g. when the focus is lost
+    /// and the releases are not received anymore.
+    pub fn clear_mods(&self) {
+        self.mods.set(key::Mods::default());
+    }
+
+    /// Sets the held modifiers from the state of the keys, given with
+    /// one bit per keycode as in the reply of `QueryKeymap`.
+    pub fn reset_mods(&self, keys: &[u8]) {
+        let mut mods = key::Mods::default();
+        for xcode in 0..keys.len() * 8 {
+            if keys[xcode / 8] & (1 << (xcode % 8)) != 0 {
+                let xcode = xcode as xkb::Keycode;
+                let mod_mask = key_modifier(self.get_keycode(xcode), self.base_keysym(xcode));
+                if mod_mask != 0 {
+                    mods.set_held(mod_mask, true);
+                }
+            }
+        }
+        self.mods.set(mods);
+    }
+
+    /// Returns the physical keys held, given with one bit per keycode
+    /// as in the reply of `QueryKeymap`. Keys without a code are skipped.
+    pub fn pressed_codes(&self, keys: &[u8]) -> Vec<key::Code> {
+        (0..keys.len() * 8)
+            .filter(|&xcode| keys[xcode / 8] & (1 << (xcode % 8)) != 0)
+            .map(|xcode| self.get_keycode(xcode as xkb::Keycode))
+            .filter(|&code| code != key::Code::Unknown)
+            .collect()
+    }
+
+    /// Whether `Event::KeyRelease` carries the text of the key.
+    pub fn set_release_text(&self, enable: bool) {
+        self.release_text.set(enable);
+    }
+
+    /// The modifiers held, as tracked from the key events.
     pub fn get_mods(&self) -> key::Mods {
-        key::Mods::new(self.mods.get())
+        self.mods.get()
     }
 
+    /// Updates the XKB state (locks, layout group...) from the server.
     // for convenience, this fn takes &self, not &mut self
     pub fn update_state(&self, ev: &xcb::xkb::StateNotifyEvent) {
-        self.state.borrow_mut().update_mask(
-            ev.base_mods().bits() as xkb::ModMask,
-            ev.latched_mods().bits() as xkb::ModMask,
-            ev.locked_mods().bits() as xkb::ModMask,
-            ev.base_group() as xkb::LayoutIndex,
-            ev.latched_group() as xkb::LayoutIndex,
-            ev.locked_group() as xkb::LayoutIndex,
-        );
+        if let Mapping::Xkb { state, .. } = &mut *self.mapping.borrow_mut() {
+            state.update_mask(
+                ev.base_mods().bits() as xkb::ModMask,
+                ev.latched_mods().bits() as xkb::ModMask,
+                ev.locked_mods().bits() as xkb::ModMask,
+                ev.base_group() as xkb::LayoutIndex,
+                ev.latched_group() as xkb::LayoutIndex,
+                ev.locked_group() as xkb::LayoutIndex,
+            );
+        }
+    }
+
+    /// Returns the name of the active layout, e.g. "English (US)".
+    /// The name is empty without XKB.
+    pub fn layout_name(&self) -> String {
+        match &*self.mapping.borrow() {
+            Mapping::Xkb { keymap, state, .. } => {
+                let layout = state.serialize_layout(xkb::STATE_LAYOUT_EFFECTIVE);
+                keymap.layout_get_name(layout).to_string()
+            }
+            Mapping::Core(_) => String::new(),
+        }
+    }
+
+    /// Returns the X keycode of the physical key `code`.
+    pub fn get_xcode(&self, code: key::Code) -> Option<xkb::Keycode> {
+        if code == key::Code::Unknown {
+            return None;
+        }
+        self.keycode_table
+            .borrow()
+            .iter()
+            .position(|&c| c == code)
+            .map(|xcode| xcode as xkb::Keycode)
+    }
+
+    /// Whether the keymap comes from XKB rather than from the core mapping.
+    pub fn has_xkb(&self) -> bool {
+        matches!(&*self.mapping.borrow(), Mapping::Xkb { .. })
     }
 
+    /// The XKB device of the keyboard, or -1 without XKB.
     pub fn get_device_id(&self) -> i32 {
-        self.device_id
+        match &*self.mapping.borrow() {
+            Mapping::Xkb { device_id, .. } => *device_id,
+            Mapping::Core(_) => -1,
+        }
+    }
+
+    #[cfg(test)]
+    fn update_key(&self, xcode: xkb::Keycode, direction: xkb::KeyDirection) {
+        if let Mapping::Xkb { state, .. } = &mut *self.mapping.borrow_mut() {
+            state.update_key(xcode, direction);
+        }
     }
 
     // fn mod_active(&self, name: &str) -> bool {
@@ -165,30 +354,343 @@ impl Keyboard {
     //         .mod_index_is_active(ind, xkb::STATE_MODS_DEPRESSED)
     // }
 
+    /// Returns the keysym of the first shift level of a key, which unlike
+    /// the keysym of the events doesn't change while the key is held.
+    fn base_keysym(&self, xcode: xkb::Keycode) -> xkb::Keysym {
+        let syms = match &*self.mapping.borrow() {
+            Mapping::Xkb { keymap, state, .. } => keymap
+                .key_get_syms_by_level(xcode, state.key_get_layout(xcode), 0)
+                .first()
+                .copied(),
+            Mapping::Core(core) => core.syms(xcode as x::Keycode).first().copied(),
+        };
+        syms.unwrap_or(xkb::KEY_NoSymbol)
+    }
+
     fn get_keycode(&self, xcode: xkb::Keycode) -> key::Code {
         let xcode = xcode as usize;
-        if xcode >= self.keycode_table.len() {
-            eprintln!("keycode 0x{:x} is out of bounds", xcode);
+        let table = self.keycode_table.borrow();
+        if xcode >= table.len() {
+            #[cfg(feature = "log")]
+            log::warn!("keycode 0x{:x} is out of bounds", xcode);
             return key::Code::Unknown;
         }
-        self.keycode_table[xcode]
+        table[xcode]
     }
 
     fn get_keysym(&self, xsym: xkb::Keysym) -> key::Sym {
-        if xsym >= 0x20 && xsym < 0x80 {
-            let mut xsym = xsym;
-            if xsym >= 0x61 && xsym <= 0x7a {
-                xsym &= !(key::SYM_LATIN1_SMALL_MASK as u32);
+        let mut xsym = xsym;
+        if (xkb::KEY_a..=xkb::KEY_z).contains(&xsym) {
+            xsym &= !(key::SYM_LATIN1_SMALL_MASK as u32);
+        }
+        self.keysym_map
+            .get(&xsym)
+            .copied()
+            .unwrap_or(key::Sym::Unknown)
+    }
+}
+
+/// Returns the modifier of a modifier key, with its side, or 0.
+/// PC keyboards have no Meta key, but the keymap can make a key produce
+/// Meta (e.g. the Windows keys with the "altwin:meta_win" option): it is
+/// detected from the keysym of the first level, as the press and release
+/// must agree whatever the Shift state in between (the "us" layout gives
+/// Meta on Shift+Alt). The other modifiers are known from the key.
+fn key_modifier(code: key::Code, xsym: xkb::Keysym) -> u8 {
+    match xsym {
+        xkb::KEY_Meta_L => return key::MODS_LEFT_META,
+        xkb::KEY_Meta_R => return key::MODS_RIGHT_META,
+        _ => {}
+    }
+    match code {
+        key::Code::LeftCtrl => key::MODS_LEFT_CTRL,
+        key::Code::LeftShift => key::MODS_LEFT_SHIFT,
+        key::Code::LeftAlt => key::MODS_LEFT_ALT,
+        key::Code::LeftSuper => key::MODS_LEFT_SUPER,
+        key::Code::RightCtrl => key::MODS_RIGHT_CTRL,
+        key::Code::RightShift => key::MODS_RIGHT_SHIFT,
+        key::Code::RightAlt => key::MODS_RIGHT_ALT,
+        key::Code::RightSuper => key::MODS_RIGHT_SUPER,
+        _ => 0,
+    }
+}
+
+/// The keyboard mapping of the core protocol: a list of keysyms per keycode,
+/// of which the first two are the unshifted and shifted keysyms.
+struct CoreMapping {
+    min_keycode: u8,
+    keysyms_per_keycode: usize,
+    keysyms: Vec<x::Keysym>,
+    /// Modifier bound to the NumLock key.
+    num_lock: x::KeyButMask,
+}
+
+impl CoreMapping {
+    fn fetch(connection: &xcb::Connection) -> Result<CoreMapping> {
+        let setup = connection.get_setup();
+        let min_keycode = setup.min_keycode();
+        let keyboard = connection.send_request(&x::GetKeyboardMapping {
+            first_keycode: min_keycode,
+            count: setup.max_keycode() - min_keycode + 1,
+        });
+        let modifiers = connection.send_request(&x::GetModifierMapping {});
+        let keyboard = connection.wait_for_reply(keyboard)?;
+        let modifiers = connection.wait_for_reply(modifiers)?;
+
+        let mut mapping = CoreMapping {
+            min_keycode,
+            keysyms_per_keycode: keyboard.keysyms_per_keycode() as usize,
+            keysyms: keyboard.keysyms().to_vec(),
+            num_lock: x::KeyButMask::empty(),
+        };
+        let per_modifier = (modifiers.keycodes_per_modifier() as usize).max(1);
+        for (index, keycodes) in modifiers.keycodes().chunks(per_modifier).enumerate() {
+            if keycodes
+                .iter()
+                .any(|&xcode| mapping.syms(xcode).contains(&xkb::KEY_Num_Lock))
+            {
+                mapping.num_lock = x::KeyButMask::from_bits_truncate(1 << index);
+            }
+        }
+        Ok(mapping)
+    }
+
+    fn syms(&self, xcode: x::Keycode) -> &[x::Keysym] {
+        let start = (xcode.wrapping_sub(self.min_keycode)) as usize * self.keysyms_per_keycode;
+        self.keysyms
+            .get(start..start + self.keysyms_per_keycode)
+            .unwrap_or(&[])
+    }
+
+    /// Selects the keysym of a key for the modifier `state`, following the
+    /// rules of the core protocol for the first group.
+    fn keysym(&self, xcode: xkb::Keycode, state: x::KeyButMask) -> xkb::Keysym {
+        let syms = self.syms(xcode as x::Keycode);
+        let lower = syms.first().copied().unwrap_or(0);
+        let upper = match syms.get(1) {
+            Some(&sym) if sym != 0 => sym,
+            _ => latin1_upper(lower),
+        };
+        let shift = state.contains(x::KeyButMask::SHIFT);
+        if !self.num_lock.is_empty() && state.contains(self.num_lock) && is_keypad(upper) {
+            if shift {
+                lower
+            } else {
+                upper
             }
-            unsafe { mem::transmute(xsym) }
-        } else if xsym >= xkb::KEY_F1 && xsym <= xkb::KEY_F24 {
-            unsafe { mem::transmute((key::Sym::F1 as u32) + (xsym - xkb::KEY_F1)) }
-        } else if let Some(k) = self.keysym_map.get(&xsym) {
-            *k
+        } else if shift {
+            upper
+        } else if state.contains(x::KeyButMask::LOCK) {
+            latin1_upper(lower)
         } else {
-            key::Sym::Unknown
+            lower
+        }
+    }
+}
+
+fn is_keypad(sym: xkb::Keysym) -> bool {
+    (xkb::KEY_KP_Space..=xkb::KEY_KP_Equal).contains(&sym)
+}
+
+/// Converts the Latin-1 lowercase letters to uppercase.
+fn latin1_upper(sym: xkb::Keysym) -> xkb::Keysym {
+    match sym {
+        xkb::KEY_a..=xkb::KEY_z => sym - 0x20,
+        xkb::KEY_agrave..=xkb::KEY_thorn if sym != xkb::KEY_division => sym - 0x20,
+        _ => sym,
+    }
+}
+
+fn keysym_text(sym: xkb::Keysym) -> String {
+    std::char::from_u32(xkb::keysym_to_utf32(sym))
+        .filter(|&c| c != '\0')
+        .map(String::from)
+        .unwrap_or_default()
+}
+
+/// Builds the keycode table from the key names of `keymap`.
+fn build_keymap_keycode_table(keymap: &xkb::Keymap) -> [key::Code; 256] {
+    let mut table = build_keycode_table();
+    let max = keymap.max_keycode().min(table.len() as u32 - 1);
+    for xcode in keymap.min_keycode()..=max {
+        if let Some(code) = keymap.key_get_name(xcode).and_then(code_from_key_name) {
+            table[xcode as usize] = code;
         }
     }
+    table
+}
+
+/// Maps an XKB key name (as found in the `xkb_keycodes` section of a keymap)
+/// to the physical key it designates.
+fn code_from_key_name(name: &str) -> Option<key::Code> {
+    use key::Code as C;
+
+    // alphanumeric section, by row (E is the digits row) and column
+    const ROW_E: [key::Code; 12] = [
+        C::N1,
+        C::N2,
+        C::N3,
+        C::N4,
+        C::N5,
+        C::N6,
+        C::N7,
+        C::N8,
+        C::N9,
+        C::N0,
+        C::Minus,
+        C::Equals,
+    ];
+    const ROW_D: [key::Code; 12] = [
+        C::Q,
+        C::W,
+        C::E,
+        C::R,
+        C::T,
+        C::Y,
+        C::U,
+        C::I,
+        C::O,
+        C::P,
+        C::LeftBracket,
+        C::RightBracket,
+    ];
+    const ROW_C: [key::Code; 12] = [
+        C::A,
+        C::S,
+        C::D,
+        C::F,
+        C::G,
+        C::H,
+        C::J,
+        C::K,
+        C::L,
+        C::Semicolon,
+        C::Quote,
+        C::Backslash,
+    ];
+    const ROW_B: [key::Code; 10] = [
+        C::Z,
+        C::X,
+        C::C,
+        C::V,
+        C::B,
+        C::N,
+        C::M,
+        C::Comma,
+        C::Period,
+        C::Slash,
+    ];
+    const FN: [key::Code; 24] = [
+        C::F1,
+        C::F2,
+        C::F3,
+        C::F4,
+        C::F5,
+        C::F6,
+        C::F7,
+        C::F8,
+        C::F9,
+        C::F10,
+        C::F11,
+        C::F12,
+        C::F13,
+        C::F14,
+        C::F15,
+        C::F16,
+        C::F17,
+        C::F18,
+        C::F19,
+        C::F20,
+        C::F21,
+        C::F22,
+        C::F23,
+        C::F24,
+    ];
+    const KP: [key::Code; 10] = [
+        C::KP_0,
+        C::KP_1,
+        C::KP_2,
+        C::KP_3,
+        C::KP_4,
+        C::KP_5,
+        C::KP_6,
+        C::KP_7,
+        C::KP_8,
+        C::KP_9,
+    ];
+
+    fn index(name: &str, prefix: &str) -> Option<usize> {
+        let num: usize = name.strip_prefix(prefix)?.parse().ok()?;
+        num.checked_sub(1)
+    }
+
+    if name.len() == 4 && name.starts_with('A') {
+        if let Some(col) = index(name, "AE") {
+            return ROW_E.get(col).copied();
+        } else if let Some(col) = index(name, "AD") {
+            return ROW_D.get(col).copied();
+        } else if let Some(col) = index(name, "AC") {
+            return ROW_C.get(col).copied();
+        } else if let Some(col) = index(name, "AB") {
+            return ROW_B.get(col).copied();
+        }
+    }
+    if let Some(num) = index(name, "FK") {
+        return FN.get(num).copied();
+    }
+    if let Some(num) = name
+        .strip_prefix("KP")
+        .and_then(|n| n.parse::<usize>().ok())
+    {
+        return KP.get(num).copied();
+    }
+
+    let code = match name {
+        "ESC" => C::Escape,
+        "TLDE" => C::Grave,
+        "BKSP" => C::Backspace,
+        "TAB" => C::Tab,
+        "RTRN" => C::Enter,
+        "CAPS" => C::CapsLock,
+        "BKSL" => C::Backslash,
+        "LSGT" => C::UK_Backslash,
+        "SPCE" => C::Space,
+        "LFSH" => C::LeftShift,
+        "RTSH" => C::RightShift,
+        "LCTL" => C::LeftCtrl,
+        "RCTL" => C::RightCtrl,
+        "LALT" => C::LeftAlt,
+        "RALT" => C::RightAlt,
+        "LWIN" => C::LeftSuper,
+        "RWIN" => C::RightSuper,
+        "COMP" | "MENU" => C::Menu,
+        "PRSC" => C::PrintScreen,
+        "SCLK" => C::ScrollLock,
+        "PAUS" => C::Pause,
+        "INS" => C::Insert,
+        "HOME" => C::Home,
+        "PGUP" => C::PageUp,
+        "DELE" => C::Delete,
+        "END" => C::End,
+        "PGDN" => C::PageDown,
+        "UP" => C::Up,
+        "LEFT" => C::Left,
+        "DOWN" => C::Down,
+        "RGHT" => C::Right,
+        "NMLK" => C::KP_NumLock,
+        "KPDV" => C::KP_Divide,
+        "KPMU" => C::KP_Multiply,
+        "KPSU" => C::KP_Subtract,
+        "KPAD" => C::KP_Add,
+        "KPEN" => C::KP_Enter,
+        "KPDL" => C::KP_Period,
+        "KPEQ" => C::KP_Equal,
+        "MUTE" => C::Mute,
+        "VOL-" => C::VolumeDown,
+        "VOL+" => C::VolumeUp,
+        _ => return None,
+    };
+    Some(code)
 }
 
 fn build_keycode_table() -> [key::Code; 256] {
@@ -471,6 +973,105 @@ fn build_keycode_table() -> [key::Code; 256] {
 fn build_keysym_map() -> HashMap<u32, key::Sym> {
     let mut map = HashMap::new();
 
+    // printable ASCII (small letters are folded to capitals by get_keysym)
+
+    map.insert(xkb::KEY_space, key::Sym::space);
+    map.insert(xkb::KEY_exclam, key::Sym::exclam);
+    map.insert(xkb::KEY_quotedbl, key::Sym::quotedbl);
+    map.insert(xkb::KEY_numbersign, key::Sym::numbersign);
+    map.insert(xkb::KEY_dollar, key::Sym::dollar);
+    map.insert(xkb::KEY_percent, key::Sym::percent);
+    map.insert(xkb::KEY_ampersand, key::Sym::ampersand);
+    map.insert(xkb::KEY_apostrophe, key::Sym::apostrophe);
+    map.insert(xkb::KEY_parenleft, key::Sym::parenleft);
+    map.insert(xkb::KEY_parenright, key::Sym::parenright);
+    map.insert(xkb::KEY_asterisk, key::Sym::asterisk);
+    map.insert(xkb::KEY_plus, key::Sym::plus);
+    map.insert(xkb::KEY_comma, key::Sym::comma);
+    map.insert(xkb::KEY_minus, key::Sym::minus);
+    map.insert(xkb::KEY_period, key::Sym::period);
+    map.insert(xkb::KEY_slash, key::Sym::slash);
+    map.insert(xkb::KEY_0, key::Sym::D0);
+    map.insert(xkb::KEY_1, key::Sym::D1);
+    map.insert(xkb::KEY_2, key::Sym::D2);
+    map.insert(xkb::KEY_3, key::Sym::D3);
+    map.insert(xkb::KEY_4, key::Sym::D4);
+    map.insert(xkb::KEY_5, key::Sym::D5);
+    map.insert(xkb::KEY_6, key::Sym::D6);
+    map.insert(xkb::KEY_7, key::Sym::D7);
+    map.insert(xkb::KEY_8, key::Sym::D8);
+    map.insert(xkb::KEY_9, key::Sym::D9);
+    map.insert(xkb::KEY_colon, key::Sym::colon);
+    map.insert(xkb::KEY_semicolon, key::Sym::semicolon);
+    map.insert(xkb::KEY_less, key::Sym::less);
+    map.insert(xkb::KEY_equal, key::Sym::equal);
+    map.insert(xkb::KEY_greater, key::Sym::greater);
+    map.insert(xkb::KEY_question, key::Sym::question);
+    map.insert(xkb::KEY_at, key::Sym::at);
+    map.insert(xkb::KEY_A, key::Sym::A);
+    map.insert(xkb::KEY_B, key::Sym::B);
+    map.insert(xkb::KEY_C, key::Sym::C);
+    map.insert(xkb::KEY_D, key::Sym::D);
+    map.insert(xkb::KEY_E, key::Sym::E);
+    map.insert(xkb::KEY_F, key::Sym::F);
+    map.insert(xkb::KEY_G, key::Sym::G);
+    map.insert(xkb::KEY_H, key::Sym::H);
+    map.insert(xkb::KEY_I, key::Sym::I);
+    map.insert(xkb::KEY_J, key::Sym::J);
+    map.insert(xkb::KEY_K, key::Sym::K);
+    map.insert(xkb::KEY_L, key::Sym::L);
+    map.insert(xkb::KEY_M, key::Sym::M);
+    map.insert(xkb::KEY_N, key::Sym::N);
+    map.insert(xkb::KEY_O, key::Sym::O);
+    map.insert(xkb::KEY_P, key::Sym::P);
+    map.insert(xkb::KEY_Q, key::Sym::Q);
+    map.insert(xkb::KEY_R, key::Sym::R);
+    map.insert(xkb::KEY_S, key::Sym::S);
+    map.insert(xkb::KEY_T, key::Sym::T);
+    map.insert(xkb::KEY_U, key::Sym::U);
+    map.insert(xkb::KEY_V, key::Sym::V);
+    map.insert(xkb::KEY_W, key::Sym::W);
+    map.insert(xkb::KEY_X, key::Sym::X);
+    map.insert(xkb::KEY_Y, key::Sym::Y);
+    map.insert(xkb::KEY_Z, key::Sym::Z);
+    map.insert(xkb::KEY_bracketleft, key::Sym::bracketleft);
+    map.insert(xkb::KEY_backslash, key::Sym::backslash);
+    map.insert(xkb::KEY_bracketright, key::Sym::bracketright);
+    map.insert(xkb::KEY_asciicircum, key::Sym::asciicircum);
+    map.insert(xkb::KEY_underscore, key::Sym::underscore);
+    map.insert(xkb::KEY_grave, key::Sym::grave);
+    map.insert(xkb::KEY_braceleft, key::Sym::braceleft);
+    map.insert(xkb::KEY_bar, key::Sym::bar);
+    map.insert(xkb::KEY_braceright, key::Sym::braceright);
+    map.insert(xkb::KEY_asciitilde, key::Sym::asciitilde);
+
+    // function keys
+
+    map.insert(xkb::KEY_F1, key::Sym::F1);
+    map.insert(xkb::KEY_F2, key::Sym::F2);
+    map.insert(xkb::KEY_F3, key::Sym::F3);
+    map.insert(xkb::KEY_F4, key::Sym::F4);
+    map.insert(xkb::KEY_F5, key::Sym::F5);
+    map.insert(xkb::KEY_F6, key::Sym::F6);
+    map.insert(xkb::KEY_F7, key::Sym::F7);
+    map.insert(xkb::KEY_F8, key::Sym::F8);
+    map.insert(xkb::KEY_F9, key::Sym::F9);
+    map.insert(xkb::KEY_F10, key::Sym::F10);
+    map.insert(xkb::KEY_F11, key::Sym::F11);
+    map.insert(xkb::KEY_F12, key::Sym::F12);
+    map.insert(xkb::KEY_F13, key::Sym::F13);
+    map.insert(xkb::KEY_F14, key::Sym::F14);
+    map.insert(xkb::KEY_F15, key::Sym::F15);
+    map.insert(xkb::KEY_F16, key::Sym::F16);
+    map.insert(xkb::KEY_F17, key::Sym::F17);
+    map.insert(xkb::KEY_F18, key::Sym::F18);
+    map.insert(xkb::KEY_F19, key::Sym::F19);
+    map.insert(xkb::KEY_F20, key::Sym::F20);
+    map.insert(xkb::KEY_F21, key::Sym::F21);
+    map.insert(xkb::KEY_F22, key::Sym::F22);
+    map.insert(xkb::KEY_F23, key::Sym::F23);
+    map.insert(xkb::KEY_F24, key::Sym::F24);
+
     map.insert(xkb::KEY_Escape, key::Sym::Escape);
     map.insert(xkb::KEY_Tab, key::Sym::Tab);
     map.insert(xkb::KEY_ISO_Left_Tab, key::Sym::LeftTab);
@@ -520,8 +1121,11 @@ fn build_keysym_map() -> HashMap<u32, key::Sym> {
     map.insert(0x1005FF11, key::Sym::F12); // hardcoded Sun F37 (labeled F12)
 
     // numeric and function keypad keys
+    // the digits are produced when NumLock is on, the navigation keys when
+    // it is off, as resolved by the xkb state
 
     map.insert(xkb::KEY_KP_Enter, key::Sym::KP_Enter);
+    map.insert(xkb::KEY_KP_Insert, key::Sym::KP_Insert);
     map.insert(xkb::KEY_KP_Delete, key::Sym::KP_Delete);
     map.insert(xkb::KEY_KP_Home, key::Sym::KP_Home);
     map.insert(xkb::KEY_KP_Begin, key::Sym::KP_Begin);
@@ -545,6 +1149,7 @@ fn build_keysym_map() -> HashMap<u32, key::Sym> {
     map.insert(xkb::KEY_KP_2, key::Sym::KP_2);
     map.insert(xkb::KEY_KP_3, key::Sym::KP_3);
     map.insert(xkb::KEY_KP_4, key::Sym::KP_4);
+    map.insert(xkb::KEY_KP_5, key::Sym::KP_5);
     map.insert(xkb::KEY_KP_6, key::Sym::KP_6);
     map.insert(xkb::KEY_KP_7, key::Sym::KP_7);
     map.insert(xkb::KEY_KP_8, key::Sym::KP_8);
@@ -766,3 +1371,263 @@ fn build_keysym_map() -> HashMap<u32, key::Sym> {
 
     map
 }
+
+#[test]
+fn key_names() {
+    assert_eq!(Some(key::Code::Q), code_from_key_name("AD01"));
+    assert_eq!(Some(key::Code::Equals), code_from_key_name("AE12"));
+    assert_eq!(Some(key::Code::Slash), code_from_key_name("AB10"));
+    assert_eq!(None, code_from_key_name("AB11"));
+    assert_eq!(None, code_from_key_name("AE00"));
+    assert_eq!(Some(key::Code::F24), code_from_key_name("FK24"));
+    assert_eq!(Some(key::Code::KP_7), code_from_key_name("KP7"));
+    assert_eq!(Some(key::Code::KP_Period), code_from_key_name("KPDL"));
+    assert_eq!(Some(key::Code::RightAlt), code_from_key_name("RALT"));
+    assert_eq!(None, code_from_key_name("I253"));
+
+    // evdev keycodes, as used by the fallback table
+    let table = build_keycode_table();
+    assert_eq!(key::Code::Escape, table[9]);
+    assert_eq!(key::Code::Q, table[24]);
+}
+
+#[test]
+fn keysym_map() {
+    let map = build_keysym_map();
+    assert_eq!(Some(&key::Sym::space), map.get(&xkb::KEY_space));
+    assert_eq!(Some(&key::Sym::D7), map.get(&xkb::KEY_7));
+    assert_eq!(Some(&key::Sym::Q), map.get(&xkb::KEY_Q));
+    assert_eq!(Some(&key::Sym::asciitilde), map.get(&xkb::KEY_asciitilde));
+    assert_eq!(Some(&key::Sym::F16), map.get(&xkb::KEY_F16));
+    assert_eq!(Some(&key::Sym::F17), map.get(&xkb::KEY_F17));
+    assert_eq!(Some(&key::Sym::F24), map.get(&xkb::KEY_F24));
+}
+
+#[test]
+fn letter_case() {
+    // evdev keycodes
+    const KEY_A: xkb::Keycode = 38;
+    const KEY_LEFT_SHIFT: xkb::Keycode = 50;
+
+    let kbd = Keyboard::headless("us", "");
+    assert_eq!(
+        Event::KeyPress(
+            key::Sym::A,
+            key::Code::A,
+            "a".to_string(),
+            key::Mods::default()
+        ),
+        kbd.key_event(KEY_A, x::KeyButMask::empty(), true)
+    );
+
+    kbd.update_key(KEY_LEFT_SHIFT, xkb::KeyDirection::Down);
+    assert_eq!(
+        Event::KeyPress(
+            key::Sym::A,
+            key::Code::A,
+            "A".to_string(),
+            key::Mods::default()
+        ),
+        kbd.key_event(KEY_A, x::KeyButMask::empty(), true)
+    );
+}
+
+#[test]
+fn keypad_num_lock() {
+    // evdev keycodes
+    const KEY_NUM_LOCK: xkb::Keycode = 77;
+    const KEY_KP_5: xkb::Keycode = 84;
+    const KEY_KP_0: xkb::Keycode = 90;
+
+    let toggle_num_lock = |kbd: &Keyboard| {
+        kbd.update_key(KEY_NUM_LOCK, xkb::KeyDirection::Down);
+        kbd.update_key(KEY_NUM_LOCK, xkb::KeyDirection::Up);
+    };
+
+    let kbd = Keyboard::headless("us", "");
+    assert!(matches!(
+        kbd.key_event(KEY_KP_5, x::KeyButMask::empty(), true),
+        Event::KeyPress(key::Sym::KP_Begin, key::Code::KP_5, ..)
+    ));
+    assert!(matches!(
+        kbd.key_event(KEY_KP_0, x::KeyButMask::empty(), true),
+        Event::KeyPress(key::Sym::KP_Insert, key::Code::KP_0, ..)
+    ));
+
+    toggle_num_lock(&kbd);
+    assert!(matches!(
+        kbd.key_event(KEY_KP_5, x::KeyButMask::empty(), true),
+        Event::KeyPress(key::Sym::KP_5, key::Code::KP_5, text, _) if text == "5"
+    ));
+    assert!(matches!(
+        kbd.key_event(KEY_KP_0, x::KeyButMask::empty(), true),
+        Event::KeyPress(key::Sym::KP_0, key::Code::KP_0, text, _) if text == "0"
+    ));
+
+    toggle_num_lock(&kbd);
+    assert!(matches!(
+        kbd.key_event(KEY_KP_5, x::KeyButMask::empty(), true),
+        Event::KeyPress(key::Sym::KP_Begin, key::Code::KP_5, ..)
+    ));
+}
+
+#[test]
+fn headless_key_events() {
+    use xcb::x;
+    use xcb::XidNew;
+
+    let key_event = |detail| {
+        let win = x::Window::new(1);
+        x::KeyPressEvent::new(
+            detail,
+            0,
+            win,
+            win,
+            x::WINDOW_NONE,
+            0,
+            0,
+            0,
+            0,
+            x::KeyButMask::empty(),
+            true,
+        )
+    };
+
+    let kbd = Keyboard::headless("fr", "");
+    // the physical Q key types 'a' on an AZERTY layout
+    assert!(matches!(
+        kbd.make_key_event(&key_event(24), true),
+        Event::KeyPress(key::Sym::A, key::Code::Q, text, _) if text == "a"
+    ));
+
+    // modifier keys are tracked with their side
+    kbd.make_key_event(&key_event(105), true);
+    assert_eq!(key::Mods::new(key::MODS_RIGHT_CTRL), kbd.get_mods());
+    kbd.make_key_event(&key_event(105), false);
+    assert_eq!(key::Mods::new(0), kbd.get_mods());
+}
+
+#[test]
+fn core_mapping() {
+    // keycodes 8 to 11: a, 1, KP_1 and Num_Lock, on Mod2
+    let mapping = CoreMapping {
+        min_keycode: 8,
+        keysyms_per_keycode: 2,
+        keysyms: vec![
+            xkb::KEY_a,
+            0,
+            xkb::KEY_1,
+            xkb::KEY_exclam,
+            xkb::KEY_KP_End,
+            xkb::KEY_KP_1,
+            xkb::KEY_Num_Lock,
+            0,
+        ],
+        num_lock: x::KeyButMask::MOD2,
+    };
+    let shift = x::KeyButMask::SHIFT;
+    let lock = x::KeyButMask::LOCK;
+    let num_lock = x::KeyButMask::MOD2;
+
+    assert_eq!(xkb::KEY_a, mapping.keysym(8, x::KeyButMask::empty()));
+    assert_eq!(xkb::KEY_A, mapping.keysym(8, shift));
+    assert_eq!(xkb::KEY_A, mapping.keysym(8, lock));
+    assert_eq!(xkb::KEY_1, mapping.keysym(9, lock));
+    assert_eq!(xkb::KEY_exclam, mapping.keysym(9, shift));
+    assert_eq!(xkb::KEY_KP_End, mapping.keysym(10, x::KeyButMask::empty()));
+    assert_eq!(xkb::KEY_KP_1, mapping.keysym(10, num_lock));
+    assert_eq!(xkb::KEY_KP_End, mapping.keysym(10, num_lock | shift));
+    assert_eq!(0, mapping.keysym(200, x::KeyButMask::empty()));
+
+    assert_eq!("A", keysym_text(xkb::KEY_A));
+    assert_eq!("1", keysym_text(xkb::KEY_KP_1));
+    assert_eq!("", keysym_text(xkb::KEY_KP_End));
+}
+
+#[test]
+fn mods_after_focus_change() {
+    // evdev keycodes
+    const KEY_LEFT_CTRL: xkb::Keycode = 37;
+    const KEY_RIGHT_SHIFT: xkb::Keycode = 62;
+
+    let kbd = Keyboard::headless("us", "");
+    kbd.key_event(KEY_LEFT_CTRL, x::KeyButMask::empty(), true);
+    assert_eq!(key::Mods::new(key::MODS_LEFT_CTRL), kbd.get_mods());
+
+    // the focus is lost with Ctrl held, and the release goes elsewhere
+    kbd.clear_mods();
+    assert_eq!(key::Mods::default(), kbd.get_mods());
+
+    // the focus comes back with right Shift held
+    let mut keys = [0u8; 32];
+    keys[KEY_RIGHT_SHIFT as usize / 8] |= 1 << (KEY_RIGHT_SHIFT % 8);
+    kbd.reset_mods(&keys);
+    assert_eq!(key::Mods::new(key::MODS_RIGHT_SHIFT), kbd.get_mods());
+}
+
+#[test]
+fn pressed_codes() {
+    // evdev keycodes
+    const KEY_A: xkb::Keycode = 38;
+    const KEY_RIGHT_SHIFT: xkb::Keycode = 62;
+
+    let kbd = Keyboard::headless("us", "");
+    let mut keys = [0u8; 32];
+    assert!(kbd.pressed_codes(&keys).is_empty());
+
+    keys[KEY_A as usize / 8] |= 1 << (KEY_A % 8);
+    keys[KEY_RIGHT_SHIFT as usize / 8] |= 1 << (KEY_RIGHT_SHIFT % 8);
+    // keycodes below 8 are never used
+    keys[0] |= 1;
+    assert_eq!(
+        vec![key::Code::A, key::Code::RightShift],
+        kbd.pressed_codes(&keys)
+    );
+}
+
+#[test]
+fn meta_modifier() {
+    // evdev keycodes
+    const KEY_LEFT_ALT: xkb::Keycode = 64;
+    const KEY_LEFT_SUPER: xkb::Keycode = 133;
+
+    let kbd = Keyboard::headless("us", "");
+    kbd.key_event(KEY_LEFT_SUPER, x::KeyButMask::empty(), true);
+    assert_eq!(key::Mods::new(key::MODS_LEFT_SUPER), kbd.get_mods());
+
+    let kbd = Keyboard::headless("us", "altwin:meta_win");
+    kbd.key_event(KEY_LEFT_SUPER, x::KeyButMask::empty(), true);
+    kbd.key_event(KEY_LEFT_ALT, x::KeyButMask::empty(), true);
+    assert_eq!(
+        key::Mods::new(key::MODS_LEFT_META | key::MODS_LEFT_ALT),
+        kbd.get_mods()
+    );
+    assert!(kbd.get_mods().has_meta());
+    kbd.key_event(KEY_LEFT_SUPER, x::KeyButMask::empty(), false);
+    assert_eq!(key::Mods::new(key::MODS_LEFT_ALT), kbd.get_mods());
+}
+
+#[test]
+fn meta_modifier_shift_level() {
+    // evdev keycodes
+    const KEY_LEFT_SHIFT: xkb::Keycode = 50;
+    const KEY_LEFT_ALT: xkb::Keycode = 64;
+
+    // Alt is [Alt_L, Meta_L]: the shift level changes while Alt is held
+    let kbd = Keyboard::headless("us", "");
+    kbd.update_key(KEY_LEFT_SHIFT, xkb::KeyDirection::Down);
+    kbd.key_event(KEY_LEFT_SHIFT, x::KeyButMask::empty(), true);
+    kbd.update_key(KEY_LEFT_ALT, xkb::KeyDirection::Down);
+    kbd.key_event(KEY_LEFT_ALT, x::KeyButMask::empty(), true);
+    assert_eq!(
+        key::Mods::new(key::MODS_LEFT_SHIFT | key::MODS_LEFT_ALT),
+        kbd.get_mods()
+    );
+
+    kbd.update_key(KEY_LEFT_SHIFT, xkb::KeyDirection::Up);
+    kbd.key_event(KEY_LEFT_SHIFT, x::KeyButMask::empty(), false);
+    kbd.update_key(KEY_LEFT_ALT, xkb::KeyDirection::Up);
+    kbd.key_event(KEY_LEFT_ALT, x::KeyButMask::empty(), false);
+    assert_eq!(key::Mods::default(), kbd.get_mods());
+    assert!(!kbd.get_mods().has_meta());
+}
@@ -1,15 +1,22 @@
 // This file is part of toy_xcb and is released under the terms
 // of the MIT license. See included LICENSE.txt file.
 
+mod dnd;
 mod error;
-mod keyboard;
+mod present;
 
+pub mod display;
 pub mod event;
 pub mod geometry;
 pub mod key;
+pub mod keyboard;
+pub mod monitor;
 pub mod mouse;
 pub mod window;
 
+pub use display::Display;
 pub use error::{Error, Result};
 pub use event::Event;
-pub use window::Window;
+pub use keyboard::Keyboard;
+pub use monitor::Monitor;
+pub use window::{Window, WindowBuilder, WindowHandle};
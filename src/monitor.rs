@@ -0,0 +1,195 @@
+// This file is part of toy_xcb and is released under the terms
+// of the MIT license. See included LICENSE.txt file.
+
+use super::geometry::{IPoint, IRect};
+use super::{Error, Result};
+
+use xcb::{randr, x, Xid};
+
+/// A physical monitor as reported by RandR.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Monitor {
+    pub name: String,
+    /// Geometry of the monitor in root window coordinates.
+    pub rect: IRect,
+    pub primary: bool,
+    // fields per second and dots per field of the current mode
+    refresh: Option<(u64, u32)>,
+}
+
+impl Monitor {
+    /// Refresh rate of the monitor, computed from its current mode.
+    /// Returns `None` if the mode is not known, e.g. for a monitor
+    /// that spans no active output.
+    pub fn refresh_hz(&self) -> Option<f32> {
+        self.refresh
+            .map(|(dot_clock, dots)| (dot_clock as f64 / dots as f64) as f32)
+    }
+}
+
+pub(crate) fn get_monitors(conn: &xcb::Connection, window: x::Window) -> Result<Vec<Monitor>> {
+    if !conn
+        .active_extensions()
+        .any(|ext| ext == xcb::Extension::RandR)
+    {
+        return Err(Error::Unsupported("RandR extension is not available"));
+    }
+
+    // GetMonitors appeared in RandR 1.5
+    let ver = conn.wait_for_reply(conn.send_request(&randr::QueryVersion {
+        major_version: 1,
+        minor_version: 5,
+    }))?;
+    if (ver.major_version(), ver.minor_version()) < (1, 5) {
+        return Err(Error::Unsupported("RandR 1.5 is required to list monitors"));
+    }
+
+    let reply = conn.wait_for_reply(conn.send_request(&randr::GetMonitors {
+        window,
+        get_active: true,
+    }))?;
+
+    let res =
+        conn.wait_for_reply(conn.send_request(&randr::GetScreenResourcesCurrent { window }))?;
+
+    // send all the requests before waiting any reply
+    let cookies: Vec<_> = reply
+        .monitors()
+        .map(|mon| {
+            let name = conn.send_request(&x::GetAtomName { atom: mon.name() });
+            let output = mon.outputs().first().map(|&output| {
+                conn.send_request(&randr::GetOutputInfo {
+                    output,
+                    config_timestamp: res.config_timestamp(),
+                })
+            });
+            (name, output)
+        })
+        .collect();
+
+    let mut monitors = Vec::with_capacity(cookies.len());
+    for (mon, (name, output)) in reply.monitors().zip(cookies) {
+        let name = conn.wait_for_reply(name)?;
+        let refresh = match output {
+            Some(output) => {
+                let output = conn.wait_for_reply(output)?;
+                get_crtc_refresh(conn, &res, output.crtc())?
+            }
+            None => None,
+        };
+        monitors.push(Monitor {
+            name: name.name().to_utf8().into_owned(),
+            rect: IRect::new(
+                mon.x() as i32,
+                mon.y() as i32,
+                mon.width() as i32,
+                mon.height() as i32,
+            ),
+            primary: mon.primary(),
+            refresh,
+        });
+    }
+
+    Ok(monitors)
+}
+
+/// Returns the monitor containing `point`, in root coordinates.
+/// Where monitors overlap (cloned outputs), the first one is returned.
+pub(crate) fn monitor_at(monitors: Vec<Monitor>, point: IPoint) -> Option<Monitor> {
+    monitors.into_iter().find(|mon| mon.rect.contains(point))
+}
+
+fn get_crtc_refresh(
+    conn: &xcb::Connection,
+    res: &randr::GetScreenResourcesCurrentReply,
+    crtc: randr::Crtc,
+) -> Result<Option<(u64, u32)>> {
+    if crtc.is_none() {
+        return Ok(None);
+    }
+    let info = conn.wait_for_reply(conn.send_request(&randr::GetCrtcInfo {
+        crtc,
+        config_timestamp: res.config_timestamp(),
+    }))?;
+    let mode = info.mode().resource_id();
+    Ok(res
+        .modes()
+        .iter()
+        .find(|m| m.id == mode)
+        .and_then(mode_refresh))
+}
+
+/// Returns the dot clock and the number of dots per refresh of `mode`,
+/// both scaled such that their ratio is the refresh rate.
+fn mode_refresh(mode: &randr::ModeInfo) -> Option<(u64, u32)> {
+    let mut clock = mode.dot_clock as u64;
+    let mut vtotal = mode.vtotal as u32;
+    if mode.mode_flags.contains(randr::ModeFlag::DOUBLE_SCAN) {
+        vtotal *= 2;
+    }
+    if mode.mode_flags.contains(randr::ModeFlag::INTERLACE) {
+        // two fields per frame
+        clock *= 2;
+    }
+    let dots = mode.htotal as u32 * vtotal;
+    if clock == 0 || dots == 0 {
+        None
+    } else {
+        Some((clock, dots))
+    }
+}
+
+#[test]
+fn refresh_rate() {
+    // 1920x1080 at 60Hz, CEA-861 timings
+    let mut mode = randr::ModeInfo {
+        id: 1,
+        width: 1920,
+        height: 1080,
+        dot_clock: 148_500_000,
+        hsync_start: 2008,
+        hsync_end: 2052,
+        htotal: 2200,
+        hskew: 0,
+        vsync_start: 1084,
+        vsync_end: 1089,
+        vtotal: 1125,
+        name_len: 0,
+        mode_flags: randr::ModeFlag::empty(),
+    };
+    let mut mon = Monitor {
+        name: "HDMI-1".to_string(),
+        rect: IRect::new(0, 0, 1920, 1080),
+        primary: true,
+        refresh: mode_refresh(&mode),
+    };
+    assert_eq!(Some(60.0), mon.refresh_hz());
+
+    mode.mode_flags = randr::ModeFlag::INTERLACE;
+    mon.refresh = mode_refresh(&mode);
+    assert_eq!(Some(120.0), mon.refresh_hz());
+
+    mode.dot_clock = 0;
+    mon.refresh = mode_refresh(&mode);
+    assert_eq!(None, mon.refresh_hz());
+}
+
+#[test]
+fn monitor_at_point() {
+    let monitor = |name: &str, rect| Monitor {
+        name: name.to_string(),
+        rect,
+        primary: false,
+        refresh: None,
+    };
+    let monitors = vec![
+        monitor("DP-1", IRect::new(0, 0, 1920, 1080)),
+        monitor("DP-2", IRect::new(1920, 0, 1280, 1024)),
+    ];
+    let name = |point| monitor_at(monitors.clone(), point).map(|mon| mon.name);
+
+    assert_eq!(Some("DP-1".to_string()), name(IPoint::new(1919, 500)));
+    assert_eq!(Some("DP-2".to_string()), name(IPoint::new(1920, 500)));
+    // below the smaller monitor
+    assert_eq!(None, name(IPoint::new(2000, 1050)));
+}
@@ -3,10 +3,64 @@
 
 use bitflags::bitflags;
 
+/// A mouse button, as reported by a press or a release.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Button {
+    Left,
+    Middle,
+    Right,
+    /// The "back" side button, usually under the thumb.
+    Back,
+    /// The "forward" side button.
+    Forward,
+    /// Any other button, with its X number.
+    /// Buttons 4 to 7 are the scroll wheel, in each direction.
+    Other(u8),
+}
+
+impl Button {
+    /// Returns the button of the X number `detail`, as in the `detail`
+    /// field of the button events.
+    pub fn from_detail(detail: u8) -> Button {
+        match detail {
+            1 => Button::Left,
+            2 => Button::Middle,
+            3 => Button::Right,
+            8 => Button::Back,
+            9 => Button::Forward,
+            other => Button::Other(other),
+        }
+    }
+
+    /// Returns the bits of the button in `Buttons`,
+    /// 0 for the buttons that `Buttons` doesn't track.
+    pub fn to_mask(self) -> u8 {
+        match self {
+            Button::Left => Buttons::LEFT.bits(),
+            Button::Middle => Buttons::MIDDLE.bits(),
+            Button::Right => Buttons::RIGHT.bits(),
+            _ => 0,
+        }
+    }
+}
+
 bitflags! {
+    /// The mouse buttons held.
     pub struct Buttons: u8 {
        const LEFT = 1;
        const MIDDLE = 2;
        const RIGHT = 4;
     }
 }
+
+#[test]
+fn button_detail() {
+    assert_eq!(Button::Left, Button::from_detail(1));
+    assert_eq!(Button::Forward, Button::from_detail(9));
+    // scroll up
+    assert_eq!(Button::Other(4), Button::from_detail(4));
+
+    assert_eq!(Buttons::RIGHT.bits(), Button::from_detail(3).to_mask());
+    assert_eq!(0, Button::Back.to_mask());
+    assert_eq!(0, Button::Other(4).to_mask());
+}
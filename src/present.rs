@@ -0,0 +1,239 @@
+// This file is part of toy_xcb and is released under the terms
+// of the MIT license. See included LICENSE.txt file.
+
+use super::display::{Display, ImageFormat};
+use super::geometry::ISize;
+use super::{Error, Result};
+
+use std::convert::TryFrom;
+use std::rc::Rc;
+
+use xcb::{x, Xid};
+
+/// Size in bytes of the `PutImage` request header.
+const PUT_IMAGE_HEADER_LEN: usize = 24;
+
+/// Checks that `size` fits in the 16 bits dimensions of the protocol
+/// and that `buffer` holds at least `size.w * size.h` pixels.
+fn check_buffer(buffer: &[u32], size: ISize) -> Result<()> {
+    let dim = |d: i32| u16::try_from(d).ok().map(usize::from);
+    let len = dim(size.w)
+        .zip(dim(size.h))
+        .and_then(|(w, h)| w.checked_mul(h))
+        .ok_or(Error::InvalidArgument("present size is out of range"))?;
+    if buffer.len() < len {
+        return Err(Error::InvalidArgument("present buffer is too small"));
+    }
+    Ok(())
+}
+
+/// Returns the number of columns and rows of the tiles in which an image
+/// of `width` by `height` pixels is uploaded, so that each `PutImage`
+/// fits in `max_len` bytes. Rows are only split when a single row doesn't fit.
+fn upload_tiles(
+    format: &ImageFormat,
+    width: usize,
+    height: usize,
+    max_len: usize,
+) -> (usize, usize) {
+    let avail = max_len - PUT_IMAGE_HEADER_LEN;
+    let mut cols = width;
+    if format.stride(cols) > avail {
+        cols = avail * 8 / format.bits_per_pixel as usize;
+        while format.stride(cols) > avail {
+            cols -= 1;
+        }
+    }
+    let rows = (avail / format.stride(cols)).max(1).min(height);
+    (cols, rows)
+}
+
+/// Pushes CPU rendered pixels to a window through a server side pixmap.
+/// The pixmap and the graphics context are freed on drop.
+pub(crate) struct Presenter {
+    dpy: Rc<Display>,
+    format: ImageFormat,
+    gc: x::Gcontext,
+    pixmap: x::Pixmap,
+    size: ISize,
+}
+
+impl Presenter {
+    /// Checks that the visual is a 24 or 32 bits TrueColor visual in the
+    /// usual 0xRRGGBB layout and creates the graphics context.
+    pub fn new(
+        dpy: Rc<Display>,
+        win: x::Window,
+        screen: usize,
+        visual: x::Visualid,
+    ) -> Result<Presenter> {
+        let conn = &dpy.conn;
+        let setup = conn.get_setup();
+        let screen = setup
+            .roots()
+            .nth(screen)
+            .ok_or(Error::InvalidScreen(screen))?;
+
+        let (depth, vis) = screen
+            .allowed_depths()
+            .find_map(|d| {
+                d.visuals()
+                    .iter()
+                    .find(|v| v.visual_id() == visual)
+                    .map(|v| (d.depth(), *v))
+            })
+            .ok_or(Error::Unsupported("window visual not found"))?;
+
+        if vis.class() != x::VisualClass::TrueColor {
+            return Err(Error::Unsupported("present requires a TrueColor visual"));
+        }
+        if depth != 24 && depth != 32 {
+            return Err(Error::Unsupported(
+                "present requires a 24 or 32 bits visual",
+            ));
+        }
+        if vis.red_mask() != 0xff0000 || vis.green_mask() != 0xff00 || vis.blue_mask() != 0xff {
+            return Err(Error::Unsupported("present requires a 0xRRGGBB visual"));
+        }
+        let format = ImageFormat::from_setup(setup, depth)
+            .filter(|f| f.bits_per_pixel == 24 || f.bits_per_pixel == 32)
+            .ok_or(Error::Unsupported(
+                "present requires 24 or 32 bits per pixel images",
+            ))?;
+
+        let gc = conn.generate_id();
+        conn.check_request(conn.send_request_checked(&x::CreateGc {
+            cid: gc,
+            drawable: x::Drawable::Window(win),
+            value_list: &[x::Gc::GraphicsExposures(false)],
+        }))?;
+
+        Ok(Presenter {
+            dpy,
+            format,
+            gc,
+            pixmap: x::Pixmap::none(),
+            size: ISize::new(0, 0),
+        })
+    }
+
+    pub fn present(&mut self, win: x::Window, buffer: &[u32], size: ISize) -> Result<()> {
+        check_buffer(buffer, size)?;
+        let conn = &self.dpy.conn;
+        if size.w == 0 || size.h == 0 {
+            return Ok(());
+        }
+
+        if size != self.size {
+            if !self.pixmap.is_none() {
+                conn.send_request(&x::FreePixmap {
+                    pixmap: self.pixmap,
+                });
+            }
+            self.pixmap = conn.generate_id();
+            conn.send_request(&x::CreatePixmap {
+                depth: self.format.depth,
+                pid: self.pixmap,
+                drawable: x::Drawable::Window(win),
+                width: size.w as u16,
+                height: size.h as u16,
+            });
+            self.size = size;
+        }
+
+        let width = size.w as usize;
+        let height = size.h as usize;
+        let max_len = conn.get_maximum_request_length() as usize * 4;
+        let (tile_cols, tile_rows) = upload_tiles(&self.format, width, height, max_len);
+
+        let mut data = Vec::with_capacity(tile_rows * self.format.stride(tile_cols));
+        for x in (0..width).step_by(tile_cols) {
+            let cols = tile_cols.min(width - x);
+            for y in (0..height).step_by(tile_rows) {
+                let rows = tile_rows.min(height - y);
+                data.clear();
+                for row in y..y + rows {
+                    let start = row * width + x;
+                    self.format
+                        .encode_row(&buffer[start..start + cols], &mut data);
+                }
+                conn.send_request(&x::PutImage {
+                    format: x::ImageFormat::ZPixmap,
+                    drawable: x::Drawable::Pixmap(self.pixmap),
+                    gc: self.gc,
+                    width: cols as u16,
+                    height: rows as u16,
+                    dst_x: x as i16,
+                    dst_y: y as i16,
+                    left_pad: 0,
+                    depth: self.format.depth,
+                    data: &data,
+                });
+            }
+        }
+
+        conn.send_request(&x::CopyArea {
+            src_drawable: x::Drawable::Pixmap(self.pixmap),
+            dst_drawable: x::Drawable::Window(win),
+            gc: self.gc,
+            src_x: 0,
+            src_y: 0,
+            dst_x: 0,
+            dst_y: 0,
+            width: width as u16,
+            height: height as u16,
+        });
+        conn.flush()?;
+        Ok(())
+    }
+}
+
+impl Drop for Presenter {
+    fn drop(&mut self) {
+        let conn = &self.dpy.conn;
+        if !self.pixmap.is_none() {
+            conn.send_request(&x::FreePixmap {
+                pixmap: self.pixmap,
+            });
+        }
+        conn.send_request(&x::FreeGc { gc: self.gc });
+        // errors are reported by the next request that checks them
+        let _ = conn.flush();
+    }
+}
+
+#[test]
+fn buffer_size() {
+    let buffer = vec![0u32; 12];
+    assert!(check_buffer(&buffer, ISize::new(4, 3)).is_ok());
+    assert!(check_buffer(&buffer, ISize::new(0, 3)).is_ok());
+    assert!(matches!(
+        check_buffer(&buffer, ISize::new(5, 3)),
+        Err(Error::InvalidArgument(_))
+    ));
+    // the product wraps negative in i32
+    assert!(matches!(
+        check_buffer(&buffer, ISize::new(-4, -3)),
+        Err(Error::InvalidArgument(_))
+    ));
+    assert!(matches!(
+        check_buffer(&buffer, ISize::new(65536, 1)),
+        Err(Error::InvalidArgument(_))
+    ));
+}
+
+#[test]
+fn tiles() {
+    let format = ImageFormat {
+        depth: 24,
+        bits_per_pixel: 32,
+        scanline_pad: 32,
+        byte_order: x::ImageOrder::LsbFirst,
+    };
+    // whole rows, as many as fit
+    assert_eq!((100, 10), upload_tiles(&format, 100, 50, 24 + 4000));
+    assert_eq!((100, 50), upload_tiles(&format, 100, 50, 1 << 20));
+    // a single row doesn't fit: rows are split
+    assert_eq!((250, 1), upload_tiles(&format, 65535, 50, 24 + 1000));
+    assert_eq!((65535, 1), upload_tiles(&format, 65535, 1, 1 << 20));
+}
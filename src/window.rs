@@ -1,16 +1,54 @@
 // This file is part of toy_xcb and is released under the terms
 // of the MIT license. See included LICENSE.txt file.
 
+use super::display::{ConnectionInfo, Display, ExtensionBases, ImageFormat};
+use super::dnd::{self, Drag};
 use super::event::Event;
-use super::geometry::IPoint;
+use super::geometry::{FPoint, IPoint, IRect, ISize};
 use super::key;
 use super::keyboard::Keyboard;
+use super::monitor::{self, Monitor};
 use super::mouse;
-use super::Result;
+use super::present::Presenter;
+use super::{Error, Result};
 
+use xcb::present;
+use xcb::randr;
+use xcb::shape;
+use xcb::sync;
 use xcb::x;
+use xcb::xfixes;
+use xcb::xinput;
 use xcb::xkb;
-use xcb::{self, Xid};
+use xcb::xtest;
+use xcb::{self, BaseEvent, Xid, XidNew};
+
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, VecDeque};
+use std::convert::TryFrom;
+use std::env;
+use std::io;
+use std::iter;
+use std::mem;
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+use std::process;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "async")]
+use std::os::unix::io::RawFd;
+#[cfg(feature = "async")]
+use tokio::io::unix::AsyncFd;
+
+#[cfg(feature = "raw-window-handle")]
+use raw_window_handle as rwh;
+#[cfg(feature = "raw-window-handle")]
+use std::ffi::c_void;
+#[cfg(feature = "raw-window-handle")]
+use std::num::NonZeroU32;
+#[cfg(feature = "raw-window-handle")]
+use std::ptr::NonNull;
 
 xcb::atoms_struct! {
     #[derive(Copy, Clone, Debug)]
@@ -18,6 +56,10 @@ xcb::atoms_struct! {
         pub utf8_string                     => b"UTF8_STRING",
         pub wm_protocols                    => b"WM_PROTOCOLS",
         pub wm_delete_window                => b"WM_DELETE_WINDOW",
+        pub wm_take_focus                   => b"WM_TAKE_FOCUS",
+        pub wm_client_machine               => b"WM_CLIENT_MACHINE",
+        pub net_wm_ping                     => b"_NET_WM_PING",
+        pub net_wm_pid                      => b"_NET_WM_PID",
         pub wm_transient_for                => b"WM_TRANSIENT_FOR",
         pub wm_change_state                 => b"WM_CHANGE_STATE",
         pub wm_state                        => b"WM_STATE",
@@ -36,9 +78,52 @@ xcb::atoms_struct! {
         pub net_wm_state_demands_attention  => b"_NET_WM_STATE_DEMANDS_ATTENTION",
         pub net_wm_state_focused            => b"_NET_WM_STATE_FOCUSED",
         pub net_wm_name                     => b"_NET_WM_NAME",
+        pub net_wm_moveresize               => b"_NET_WM_MOVERESIZE",
+        pub net_active_window               => b"_NET_ACTIVE_WINDOW",
+        pub motif_wm_hints                  => b"_MOTIF_WM_HINTS",
+        pub net_wm_window_opacity           => b"_NET_WM_WINDOW_OPACITY",
+        pub targets                         => b"TARGETS",
+        pub toy_xcb_selection               => b"TOY_XCB_SELECTION",
+        pub xdnd_aware                      => b"XdndAware",
+        pub xdnd_enter                      => b"XdndEnter",
+        pub xdnd_position                   => b"XdndPosition",
+        pub xdnd_status                     => b"XdndStatus",
+        pub xdnd_leave                      => b"XdndLeave",
+        pub xdnd_drop                       => b"XdndDrop",
+        pub xdnd_finished                   => b"XdndFinished",
+        pub xdnd_selection                  => b"XdndSelection",
+        pub xdnd_type_list                  => b"XdndTypeList",
+        pub xdnd_action_copy                => b"XdndActionCopy",
+        pub text_uri_list                   => b"text/uri-list",
     }
 }
 
+// number of 32 bits fields in WM_HINTS
+const WM_HINTS_LEN: usize = 9;
+const WM_HINTS_INPUT: u32 = 1;
+const WM_HINTS_URGENCY: u32 = 1 << 8;
+
+const NET_WM_STATE_REMOVE: u32 = 0;
+const NET_WM_STATE_ADD: u32 = 1;
+const SOURCE_INDICATION_APPLICATION: u32 = 1;
+const NET_WM_MOVERESIZE_MOVE: u32 = 8;
+const MWM_HINTS_DECORATIONS: u32 = 1 << 1;
+const MWM_DECOR_ALL: u32 = 1;
+// WM_SIZE_HINTS flags
+const P_MIN_SIZE: u32 = 1 << 4;
+const P_MAX_SIZE: u32 = 1 << 5;
+// core protocol event codes, as used by XTEST
+const KEY_PRESS: u8 = 2;
+const KEY_RELEASE: u8 = 3;
+const BUTTON_PRESS: u8 = 4;
+const BUTTON_RELEASE: u8 = 5;
+// ICCCM WM_STATE values
+const WM_STATE_NORMAL: u32 = 1;
+const WM_STATE_ICONIC: u32 = 3;
+
+// how long to wait for the owner of a selection to answer
+const SELECTION_TIMEOUT: Duration = Duration::from_secs(2);
+
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub enum State {
     Normal,
@@ -48,202 +133,3161 @@ pub enum State {
     Hidden,
 }
 
-pub struct Window {
-    conn: xcb::Connection,
+/// Edge or corner grabbed by an interactive resize.
+/// The values are the `_NET_WM_MOVERESIZE` directions.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum ResizeEdge {
+    TopLeft = 0,
+    Top = 1,
+    TopRight = 2,
+    Right = 3,
+    BottomRight = 4,
+    Bottom = 5,
+    BottomLeft = 6,
+    Left = 7,
+}
+
+/// Events selected on the window by default.
+pub const DEFAULT_EVENT_MASK: x::EventMask = x::EventMask::from_bits_truncate(
+    x::EventMask::KEY_PRESS.bits()
+        | x::EventMask::KEY_RELEASE.bits()
+        | x::EventMask::BUTTON_PRESS.bits()
+        | x::EventMask::BUTTON_RELEASE.bits()
+        | x::EventMask::ENTER_WINDOW.bits()
+        | x::EventMask::LEAVE_WINDOW.bits()
+        | x::EventMask::POINTER_MOTION.bits()
+        | x::EventMask::BUTTON_MOTION.bits()
+        | x::EventMask::EXPOSURE.bits()
+        | x::EventMask::STRUCTURE_NOTIFY.bits()
+        | x::EventMask::PROPERTY_CHANGE.bits(),
+);
+
+// events needed to track the size and the state of the window,
+// and the modifiers across focus changes
+const REQUIRED_EVENT_MASK: x::EventMask = x::EventMask::from_bits_truncate(
+    x::EventMask::STRUCTURE_NOTIFY.bits()
+        | x::EventMask::PROPERTY_CHANGE.bits()
+        | x::EventMask::FOCUS_CHANGE.bits(),
+);
+
+/// Whether the server retains the contents of the window while they are
+/// obscured or unmapped.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum BackingStore {
+    NotUseful,
+    WhenMapped,
+    Always,
+}
+
+impl BackingStore {
+    fn to_xcb(self) -> x::BackingStore {
+        match self {
+            BackingStore::NotUseful => x::BackingStore::NotUseful,
+            BackingStore::WhenMapped => x::BackingStore::WhenMapped,
+            BackingStore::Always => x::BackingStore::Always,
+        }
+    }
+}
+
+/// The window manager protocols a window can take part in,
+/// advertised in `WM_PROTOCOLS`.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Protocol {
+    /// `WM_DELETE_WINDOW`: closing the window emits `Event::Close`
+    /// rather than killing the connection.
+    DeleteWindow,
+    /// `_NET_WM_PING`: the window manager checks that the application
+    /// still responds, which is answered while processing events.
+    Ping,
+    /// `WM_TAKE_FOCUS`: the window manager asks the window to take the
+    /// focus (see `WindowBuilder::accepts_input`).
+    TakeFocus,
+}
+
+impl Protocol {
+    fn atom(self, atoms: &Atoms) -> x::Atom {
+        match self {
+            Protocol::DeleteWindow => atoms.wm_delete_window,
+            Protocol::Ping => atoms.net_wm_ping,
+            Protocol::TakeFocus => atoms.wm_take_focus,
+        }
+    }
+}
+
+type EventFilter = dyn FnMut(&xcb::Event) -> bool;
+
+/// State shared between a `Window` and its `WindowHandle`s.
+struct Shared {
+    dpy: Rc<Display>,
     atoms: Atoms,
-    def_screen: i32,
+    win: x::Window,
+    root: x::Window,
+    screen: usize,
+    visual: x::Visualid,
+    depth: u8,
+    colormap: x::Colormap,
+    title: RefCell<String>,
+    atom_cache: RefCell<HashMap<String, x::Atom>>,
+    // number of nested `Window::batch` calls
+    batch_depth: Cell<u32>,
+}
+
+pub struct Window {
+    // connection registered in the tokio reactor by `next_event`,
+    // declared first to be dropped before the connection is closed
+    #[cfg(feature = "async")]
+    async_fd: RefCell<Option<Rc<AsyncFd<RawFd>>>>,
+    shared: Rc<Shared>,
     kbd: Keyboard,
 
-    win: x::Window,
+    size: Cell<ISize>,
+    // whether the initial size is still to be reported
+    initial_resize: Cell<bool>,
+    // position of the window in root coordinates
+    position: Cell<IPoint>,
+    // move received along with a resize, reported after it
+    pending_move: Cell<Option<IPoint>>,
+    override_redirect: bool,
+    // whether the first MapNotify was received
+    mapped: Cell<bool>,
+    coalesce_redraws: bool,
+    configure_events: bool,
+    reject_synthetic_input: bool,
+    media_key_events: bool,
+    pending_ev: RefCell<VecDeque<xcb::Event>>,
+    presenter: RefCell<Option<Presenter>>,
+    // whether `present_pixmap` selected the Present events
+    present_ready: Cell<bool>,
+    // serial of the last `present_pixmap`
+    present_serial: Cell<u32>,
+    // pixel values of the colors passed to `alloc_color`
+    colors: RefCell<HashMap<u32, u32>>,
+    // text of the selections owned by this window
+    selections: RefCell<HashMap<x::Atom, String>>,
+    drag: Cell<Option<Drag>>,
+    event_filter: RefCell<Option<Box<EventFilter>>>,
+    // time of the last key or button event
+    last_time: Cell<x::Timestamp>,
+}
+
+/// Builder for `Window`, for settings that must be known before the
+/// connection and the window are created.
+pub struct WindowBuilder {
+    width: u16,
+    height: u16,
     title: String,
+    use_xlib: bool,
+    screen: Option<usize>,
+    class: Option<(String, String)>,
+    decorated: bool,
+    accepts_input: bool,
+    protocols: Vec<Protocol>,
+    override_redirect: bool,
+    parent: Option<x::Window>,
+    position: IPoint,
+    backing_store: Option<BackingStore>,
+    event_mask: x::EventMask,
+    motion_hints: bool,
+    display: Option<String>,
 }
 
-impl Window {
-    pub fn new(width: u16, height: u16, title: String) -> Result<Window> {
-        let (conn, def_screen) =
-            xcb::Connection::connect_with_xlib_display_and_extensions(&[xcb::Extension::Xkb], &[])?;
-        conn.set_event_queue_owner(xcb::EventQueueOwner::Xcb);
+impl WindowBuilder {
+    pub fn new(width: u16, height: u16) -> WindowBuilder {
+        WindowBuilder {
+            width,
+            height,
+            title: String::new(),
+            use_xlib: true,
+            screen: None,
+            class: None,
+            decorated: true,
+            accepts_input: true,
+            protocols: vec![Protocol::DeleteWindow],
+            override_redirect: false,
+            parent: None,
+            position: IPoint::new(0, 0),
+            backing_store: None,
+            event_mask: DEFAULT_EVENT_MASK,
+            motion_hints: false,
+            display: None,
+        }
+    }
+
+    pub fn title(mut self, title: impl Into<String>) -> WindowBuilder {
+        self.title = title.into();
+        self
+    }
+
+    /// Whether the connection is opened through Xlib (the default).
+    /// With `false`, a plain XCB connection is used and Xlib is never touched,
+    /// which suits environments where no Xlib display can be opened.
+    pub fn use_xlib(mut self, use_xlib: bool) -> WindowBuilder {
+        self.use_xlib = use_xlib;
+        self
+    }
+
+    /// Name of the X display to connect to, such as ":1" or "host:0".
+    /// Defaults to the `DISPLAY` environment variable.
+    /// A named display is always opened with a plain XCB connection,
+    /// as if `use_xlib(false)` was set, and `build` returns
+    /// `Error::CannotOpenDisplay` if it can't be opened.
+    pub fn display(mut self, name: &str) -> WindowBuilder {
+        self.display = Some(name.to_string());
+        self
+    }
+
+    /// Index of the X screen on which the window is created.
+    /// Defaults to the default screen of the display.
+    pub fn screen(mut self, index: usize) -> WindowBuilder {
+        self.screen = Some(index);
+        self
+    }
+
+    /// Sets the `WM_CLASS` property, used by taskbars and desktop environments
+    /// to associate the window with its application.
+    /// Defaults to the name of the executable as instance, capitalized as class.
+    pub fn class(mut self, instance: &str, class: &str) -> WindowBuilder {
+        self.class = Some((instance.to_string(), class.to_string()));
+        self
+    }
+
+    /// Whether the window manager draws its decorations (title bar, borders)
+    /// around the window. Defaults to `true`.
+    pub fn decorated(mut self, decorated: bool) -> WindowBuilder {
+        self.decorated = decorated;
+        self
+    }
+
+    /// Whether the window takes the keyboard focus, following one of
+    /// the ICCCM focus models. Defaults to `true`.
+    ///
+    /// With `true`, the model is "passive": the window manager gives the
+    /// focus to the window. Adding `Protocol::TakeFocus` makes it "locally
+    /// active": the window manager may also ask the window with
+    /// `WM_TAKE_FOCUS`, to which the window answers by taking the focus.
+    /// With `false`, the model is "no input": the window never gets the
+    /// focus, which suits overlays that only display information.
+    /// The "globally active" model is not supported.
+    pub fn accepts_input(mut self, accepts_input: bool) -> WindowBuilder {
+        self.accepts_input = accepts_input;
+        self
+    }
+
+    /// The window manager protocols the window takes part in.
+    /// Defaults to `Protocol::DeleteWindow` only.
+    /// `Protocol::TakeFocus` is left out for windows that don't accept input.
+    pub fn protocols(mut self, protocols: &[Protocol]) -> WindowBuilder {
+        self.protocols = protocols.to_vec();
+        self
+    }
+
+    /// Whether the window bypasses the window manager, which then neither
+    /// decorates, places nor reparents it. This suits pop-ups such as menus
+    /// and tooltips, which must be positioned by the application.
+    /// Defaults to `false`.
+    pub fn override_redirect(mut self, override_redirect: bool) -> WindowBuilder {
+        self.override_redirect = override_redirect;
+        self
+    }
+
+    /// Creates the window as a child of `parent`, e.g. to embed it in the
+    /// window of another application, rather than as a top-level window.
+    /// A child window is not managed by the window manager, so it gets
+    /// none of the window manager properties (title, class, protocols...)
+    /// and never receives `Event::Close`. It is created with the visual
+    /// and depth of `parent`.
+    pub fn parent(mut self, parent: x::Window) -> WindowBuilder {
+        self.parent = Some(parent);
+        self
+    }
+
+    /// Position of the window relative to its parent, `(0, 0)` by default.
+    /// Window managers usually place top-level windows themselves.
+    pub fn position(mut self, position: IPoint) -> WindowBuilder {
+        self.position = position;
+        self
+    }
+
+    /// Asks the server to retain the contents of the window, so that it can
+    /// restore them itself rather than sending `Expose` events.
+    /// This is only a hint which the server may ignore, so the window must
+    /// still be able to repaint on `Expose`. When `Always` is honored,
+    /// most of the expose-driven repaints can be skipped.
+    pub fn backing_store(mut self, backing_store: BackingStore) -> WindowBuilder {
+        self.backing_store = Some(backing_store);
+        self
+    }
+
+    /// Events selected on the window, `DEFAULT_EVENT_MASK` by default.
+    /// For example, an application that doesn't track the cursor can drop
+    /// `POINTER_MOTION` and only receive motion while a button is held.
+    /// `STRUCTURE_NOTIFY`, `PROPERTY_CHANGE` and `FOCUS_CHANGE` are always
+    /// added, as the window relies on them to track its size, its state
+    /// and the modifiers.
+    pub fn event_mask(mut self, event_mask: x::EventMask) -> WindowBuilder {
+        self.event_mask = event_mask;
+        self
+    }
+
+    /// Whether pointer motion is reported through the motion hint protocol.
+    /// The server then sends a single motion event until the window queries
+    /// the pointer position, which is done when translating the event, so
+    /// at most one `MouseMove` is emitted per round-trip. This keeps a slow
+    /// consumer from falling behind a flood of motion events, at the cost
+    /// of one round-trip of latency per move and of intermediate positions.
+    /// Defaults to `false`.
+    pub fn motion_hints(mut self, motion_hints: bool) -> WindowBuilder {
+        self.motion_hints = motion_hints;
+        self
+    }
+
+    pub fn build(self) -> Result<Window> {
+        let WindowBuilder {
+            width,
+            height,
+            title,
+            use_xlib,
+            screen,
+            class,
+            decorated,
+            accepts_input,
+            protocols,
+            override_redirect,
+            parent,
+            position,
+            backing_store,
+            mut event_mask,
+            motion_hints,
+            display,
+        } = self;
+
+        // the server would fail with an obscure BadValue
+        if width == 0 || height == 0 {
+            return Err(Error::Unsupported("window dimensions must be non-zero"));
+        }
+
+        if motion_hints {
+            event_mask |= x::EventMask::POINTER_MOTION_HINT;
+        }
+
+        let dpy = Display::connect(
+            display.as_deref(),
+            use_xlib,
+            &[],
+            &[
+                // without XKB, the keyboard falls back on the core mapping
+                xcb::Extension::Xkb,
+                xcb::Extension::RandR,
+                xcb::Extension::Shape,
+                xcb::Extension::Test,
+                xcb::Extension::Input,
+                xcb::Extension::Present,
+                xcb::Extension::XFixes,
+            ],
+        )?;
+        let screen = match screen {
+            Some(screen) if screen >= dpy.screen_count() => {
+                return Err(Error::InvalidScreen(screen));
+            }
+            Some(screen) => screen,
+            // only a misconfigured server can get this wrong
+            None if dpy.default_screen() >= dpy.screen_count() => {
+                return Err(Error::Unsupported("default screen index out of range"));
+            }
+            None => dpy.default_screen(),
+        };
+        let conn = &dpy.conn;
 
-        let atoms = Atoms::intern_all(&conn)?;
+        let atoms = Atoms::intern_all(conn)?;
 
-        let kbd = Keyboard::new(&conn)?;
-        let win = {
+        let kbd = Keyboard::new(conn)?;
+        let (win, root, visual, depth, colormap) = {
             let win = conn.generate_id();
             let setup = conn.get_setup();
-            let screen = setup.roots().nth(def_screen as usize).unwrap();
+            let screen = setup
+                .roots()
+                .nth(screen)
+                .ok_or(Error::InvalidScreen(screen))?;
+            let (visual, depth, colormap) = match parent {
+                Some(parent) => {
+                    let attrs = conn.send_request(&x::GetWindowAttributes { window: parent });
+                    let geom = conn.send_request(&x::GetGeometry {
+                        drawable: x::Drawable::Window(parent),
+                    });
+                    let attrs = conn.wait_for_reply(attrs)?;
+                    let geom = conn.wait_for_reply(geom)?;
+                    (attrs.visual(), geom.depth(), attrs.colormap())
+                }
+                // the window copies the depth of the root window
+                None => (
+                    screen.root_visual(),
+                    screen.root_depth(),
+                    screen.default_colormap(),
+                ),
+            };
+
+            // the values must be in the order of their bits
+            let mut value_list = vec![x::Cw::BackPixel(screen.white_pixel())];
+            if let Some(backing_store) = backing_store {
+                value_list.push(x::Cw::BackingStore(backing_store.to_xcb()));
+            }
+            if override_redirect {
+                value_list.push(x::Cw::OverrideRedirect(true));
+            }
+            value_list.push(x::Cw::EventMask(event_mask | REQUIRED_EVENT_MASK));
 
             conn.check_request(conn.send_request_checked(&x::CreateWindow {
                 depth: x::COPY_FROM_PARENT as u8,
                 wid: win,
-                parent: screen.root(),
-                x: 0,
-                y: 0,
+                parent: parent.unwrap_or_else(|| screen.root()),
+                x: position.x as i16,
+                y: position.y as i16,
                 width,
                 height,
                 border_width: 0,
                 class: x::WindowClass::InputOutput,
-                visual: screen.root_visual(),
-                value_list: &[
-                    x::Cw::BackPixel(screen.white_pixel()),
-                    x::Cw::EventMask(
-                        x::EventMask::KEY_PRESS
-                            | x::EventMask::KEY_RELEASE
-                            | x::EventMask::BUTTON_PRESS
-                            | x::EventMask::BUTTON_RELEASE
-                            | x::EventMask::ENTER_WINDOW
-                            | x::EventMask::LEAVE_WINDOW
-                            | x::EventMask::POINTER_MOTION
-                            | x::EventMask::BUTTON_MOTION
-                            | x::EventMask::EXPOSURE
-                            | x::EventMask::STRUCTURE_NOTIFY
-                            | x::EventMask::PROPERTY_CHANGE,
-                    ),
-                ],
+                visual,
+                value_list: &value_list,
             }))?;
 
-            win
+            (win, screen.root(), visual, depth, colormap)
         };
 
-        conn.send_request(&x::ChangeProperty {
-            mode: x::PropMode::Replace,
-            window: win,
-            property: atoms.wm_protocols,
-            r#type: x::ATOM_ATOM,
-            data: &[atoms.wm_delete_window],
-        });
+        // child windows are not managed by the window manager
+        if parent.is_none() {
+            conn.send_request(&x::ChangeProperty {
+                mode: x::PropMode::Replace,
+                window: win,
+                property: atoms.wm_protocols,
+                r#type: x::ATOM_ATOM,
+                data: &wm_protocols(&atoms, &protocols, accepts_input),
+            });
+
+            let mut hints = [0u32; WM_HINTS_LEN];
+            hints[0] = WM_HINTS_INPUT;
+            hints[1] = accepts_input as u32;
+            conn.send_request(&x::ChangeProperty {
+                mode: x::PropMode::Replace,
+                window: win,
+                property: x::ATOM_WM_HINTS,
+                r#type: x::ATOM_WM_HINTS,
+                data: &hints,
+            });
+
+            // _NET_WM_PID and WM_CLIENT_MACHINE let the window manager kill
+            // the process if it doesn't answer pings
+            conn.send_request(&x::ChangeProperty {
+                mode: x::PropMode::Replace,
+                window: win,
+                property: atoms.net_wm_pid,
+                r#type: x::ATOM_CARDINAL,
+                data: &[process::id()],
+            });
+            if let Some(hostname) = hostname() {
+                conn.send_request(&x::ChangeProperty {
+                    mode: x::PropMode::Replace,
+                    window: win,
+                    property: atoms.wm_client_machine,
+                    r#type: x::ATOM_STRING,
+                    data: hostname.as_bytes(),
+                });
+            }
 
-        // setting title
-        if !title.is_empty() {
             conn.send_request(&x::ChangeProperty {
                 mode: x::PropMode::Replace,
                 window: win,
-                property: x::ATOM_WM_NAME,
-                r#type: x::ATOM_STRING,
-                data: title.as_bytes(),
+                property: atoms.xdnd_aware,
+                r#type: x::ATOM_ATOM,
+                data: &[dnd::XDND_VERSION],
+            });
+
+            let (instance, class) = class.unwrap_or_else(default_class);
+            send_class(conn, win, &instance, &class);
+
+            if !decorated {
+                conn.send_request(&x::ChangeProperty {
+                    mode: x::PropMode::Replace,
+                    window: win,
+                    property: atoms.motif_wm_hints,
+                    r#type: atoms.motif_wm_hints,
+                    data: &motif_wm_hints(false),
+                });
+            }
+
+            // setting title
+            if !title.is_empty() {
+                send_title(conn, &atoms, win, &title);
+            }
+        }
+
+        // monitors plugged, unplugged or rotated
+        if conn
+            .active_extensions()
+            .any(|ext| ext == xcb::Extension::RandR)
+        {
+            conn.send_request(&randr::SelectInput {
+                window: win,
+                enable: randr::NotifyMask::SCREEN_CHANGE,
             });
         }
 
+        // the position is relative to the parent, which for a top-level
+        // window is the root until the window manager reparents it
+        let root_position = match parent {
+            None => position,
+            Some(_) => {
+                let origin = conn.wait_for_reply(conn.send_request(&x::TranslateCoordinates {
+                    src_window: win,
+                    dst_window: root,
+                    src_x: 0,
+                    src_y: 0,
+                }))?;
+                event_point(origin.dst_x(), origin.dst_y())
+            }
+        };
+
         conn.send_request(&x::MapWindow { window: win });
         conn.flush()?;
 
         Ok(Window {
-            conn: conn,
-            atoms: atoms,
-            def_screen: def_screen,
+            shared: Rc::new(Shared {
+                dpy: Rc::new(dpy),
+                atoms,
+                win,
+                root,
+                screen,
+                visual,
+                depth,
+                colormap,
+                title: RefCell::new(title),
+                atom_cache: RefCell::new(HashMap::new()),
+                batch_depth: Cell::new(0),
+            }),
             kbd,
-            win: win,
-            title: title,
+            size: Cell::new(ISize::new(width as i32, height as i32)),
+            initial_resize: Cell::new(true),
+            mapped: Cell::new(false),
+            position: Cell::new(root_position),
+            pending_move: Cell::new(None),
+            override_redirect,
+            coalesce_redraws: false,
+            configure_events: false,
+            reject_synthetic_input: false,
+            media_key_events: false,
+            pending_ev: RefCell::new(VecDeque::new()),
+            presenter: RefCell::new(None),
+            present_ready: Cell::new(false),
+            present_serial: Cell::new(0),
+            colors: RefCell::new(HashMap::new()),
+            selections: RefCell::new(HashMap::new()),
+            drag: Cell::new(None),
+            event_filter: RefCell::new(None),
+            last_time: Cell::new(x::CURRENT_TIME),
+            #[cfg(feature = "async")]
+            async_fd: RefCell::new(None),
         })
     }
+}
+
+impl Window {
+    pub fn new(width: u16, height: u16, title: impl Into<String>) -> Result<Window> {
+        WindowBuilder::new(width, height).title(title).build()
+    }
 
+    /// Creates a window on the X display `name` (see `WindowBuilder::display`).
+    pub fn new_on_display(
+        name: &str,
+        width: u16,
+        height: u16,
+        title: impl Into<String>,
+    ) -> Result<Window> {
+        WindowBuilder::new(width, height)
+            .display(name)
+            .title(title)
+            .build()
+    }
+
+    /// Creates a window that selects the events of `mask` rather than
+    /// `DEFAULT_EVENT_MASK` (see `WindowBuilder::event_mask`).
+    pub fn new_with_mask(
+        width: u16,
+        height: u16,
+        title: impl Into<String>,
+        mask: x::EventMask,
+    ) -> Result<Window> {
+        WindowBuilder::new(width, height)
+            .title(title)
+            .event_mask(mask)
+            .build()
+    }
+
+    /// Blocks until the next event.
+    ///
+    /// The first event is always `Event::Resize` with the initial size
+    /// (`Event::Configure` if enabled with `set_configure_events`),
+    /// so that an application can size its resources on this event only.
+    /// The `Expose` follows when the window is mapped. With redraw
+    /// coalescing, the first `Event::Redraw` holds the initial size instead.
+    /// Either way, the window manager placing the window away from its
+    /// creation position is reported by `Event::Move` (or
+    /// `Event::Configure`) with the first change of geometry.
+    ///
+    /// Interrupted waits (`EINTR`) are retried, by libxcb for this function
+    /// and `poll_event`, and by `wait_event_timeout` itself. The errors
+    /// returned are therefore either X protocol errors caused by a previous
+    /// request, or `Error::ConnectionClosed`, after which waiting again is
+    /// pointless.
     pub fn wait_event(&self) -> Result<Event> {
-        let xcb_ev = self.conn.wait_for_event()?;
-        match self.translate_event(xcb_ev) {
+        if let Some(ev) = self.initial_event() {
+            return Ok(ev);
+        }
+        let pending_ev = self.pending_ev.borrow_mut().pop_front();
+        let xcb_ev = match pending_ev {
+            Some(xcb_ev) => xcb_ev,
+            None => self.shared.dpy.conn.wait_for_event()?,
+        };
+        match self.process_event(xcb_ev)? {
             Some(ev) => Ok(ev),
             None => self.wait_event(),
         }
     }
 
-    pub fn get_title(&self) -> String {
-        self.title.clone()
+    /// Iterates over the events of the window, blocking on each call
+    /// to `next` like `wait_event` does.
+    /// The iteration ends after `Event::Close` is yielded.
+    pub fn events(&self) -> impl Iterator<Item = Result<Event>> + '_ {
+        let mut closed = false;
+        iter::from_fn(move || {
+            if closed {
+                return None;
+            }
+            let ev = self.wait_event();
+            closed = matches!(ev, Ok(Event::Close));
+            Some(ev)
+        })
     }
 
-    pub fn set_title(&mut self, title: String) {
-        if title != self.title {
-            self.title = title;
-            self.conn.send_request(&x::ChangeProperty {
-                mode: x::PropMode::Replace,
-                window: self.win,
-                property: x::ATOM_WM_NAME,
-                r#type: x::ATOM_STRING,
-                data: self.title.as_bytes(),
-            });
-            self.conn.flush().unwrap(); // should probably return a result
+    /// Waits asynchronously for the next event, the async counterpart
+    /// of `wait_event`. The connection is registered in the tokio reactor
+    /// at the first call, which must therefore happen within a runtime.
+    /// As `Window` is not `Send`, the future must run on the thread
+    /// of the window, e.g. in a `LocalSet` or a current thread runtime.
+    #[cfg(feature = "async")]
+    pub async fn next_event(&self) -> Result<Event> {
+        if let Some(ev) = self.poll_event()? {
+            return Ok(ev);
+        }
+        let fd = self.async_fd()?;
+        loop {
+            let mut guard = fd.readable().await?;
+            match self.poll_event()? {
+                Some(ev) => return Ok(ev),
+                // the socket was drained without a translated event
+                None => guard.clear_ready(),
+            }
         }
     }
 
-    pub fn default_screen(&self) -> usize {
-        self.def_screen as usize
+    #[cfg(feature = "async")]
+    fn async_fd(&self) -> Result<Rc<AsyncFd<RawFd>>> {
+        let mut async_fd = self.async_fd.borrow_mut();
+        if let Some(fd) = async_fd.as_ref() {
+            return Ok(fd.clone());
+        }
+        // SAFETY: the fd stays owned by the connection, which outlives the
+        // `AsyncFd`: the registration is only kept in the `async_fd` field,
+        // dropped before `shared` holding the connection, and in the clones
+        // held by `next_event`, whose future borrows the window
+        let fd = unsafe { AsyncFd::register(self.shared.dpy.conn.as_raw_fd()) };
+        let fd = Rc::new(fd.map_err(|err| err.into_parts().1)?);
+        *async_fd = Some(fd.clone());
+        Ok(fd)
     }
 
-    fn translate_event(&self, xcb_ev: xcb::Event) -> Option<Event> {
-        match xcb_ev {
-            xcb::Event::X(x::Event::KeyPress(xcb_ev)) => {
-                Some(self.kbd.make_key_event(&xcb_ev, true))
-            }
-            xcb::Event::X(x::Event::KeyRelease(xcb_ev)) => {
-                Some(self.kbd.make_key_event(&xcb_ev, false))
-            }
-            xcb::Event::X(x::Event::ButtonPress(xcb_ev)) => {
-                let ev = self.make_mouse_event(&xcb_ev);
-                Some(Event::MousePress(ev.0, ev.1, ev.2))
-            }
-            xcb::Event::X(x::Event::ButtonRelease(xcb_ev)) => {
-                let ev = self.make_mouse_event(&xcb_ev);
-                Some(Event::MouseRelease(ev.0, ev.1, ev.2))
-            }
-            xcb::Event::X(x::Event::EnterNotify(xcb_ev)) => {
-                Some(Event::Enter(Window::make_enterleave_point(&xcb_ev)))
+    /// Returns the next event if one is available, without blocking.
+    /// Errors are the same as for `wait_event`.
+    pub fn poll_event(&self) -> Result<Option<Event>> {
+        if let Some(ev) = self.initial_event() {
+            return Ok(Some(ev));
+        }
+        loop {
+            let pending_ev = self.pending_ev.borrow_mut().pop_front();
+            let xcb_ev = match pending_ev {
+                Some(xcb_ev) => xcb_ev,
+                None => match self.shared.dpy.conn.poll_for_event()? {
+                    Some(xcb_ev) => xcb_ev,
+                    None => return Ok(None),
+                },
+            };
+            if let Some(ev) = self.process_event(xcb_ev)? {
+                return Ok(Some(ev));
             }
-            xcb::Event::X(x::Event::LeaveNotify(xcb_ev)) => {
-                Some(Event::Leave(Window::make_enterleave_point(&xcb_ev)))
+        }
+    }
+
+    /// Passes to `f` all the events available, as long as `poll_event`
+    /// returns some. This doesn't block: it returns as soon as no event
+    /// is left, which suits a loop driven by an external reactor that
+    /// calls it whenever the file descriptor of `connection` is readable.
+    pub fn drain_events(&self, mut f: impl FnMut(Event)) -> Result<()> {
+        while let Some(ev) = self.poll_event()? {
+            f(ev);
+        }
+        Ok(())
+    }
+
+    /// Waits for the next event at most for `dur`.
+    /// Returns `Ok(None)` if no event was received within that time.
+    /// An interrupted wait resumes until the deadline.
+    /// Errors are the same as for `wait_event`.
+    pub fn wait_event_timeout(&self, dur: Duration) -> Result<Option<Event>> {
+        let deadline = Instant::now() + dur;
+        loop {
+            if let Some(ev) = self.poll_event()? {
+                return Ok(Some(ev));
             }
-            xcb::Event::X(x::Event::MotionNotify(xcb_ev)) => {
-                let point = IPoint {
-                    x: xcb_ev.event_x() as _,
-                    y: xcb_ev.event_y() as _,
-                };
-                let buttons = translate_buttons(xcb_ev.state());
-                let mods = self.kbd.get_mods();
-                Some(Event::MouseMove(point, buttons, mods))
+            let now = Instant::now();
+            if now >= deadline || !self.wait_readable(deadline - now)? {
+                return Ok(None);
             }
-            xcb::Event::X(x::Event::ClientMessage(xcb_ev)) => {
-                if xcb_ev.r#type() == self.atoms.wm_protocols {
-                    if let x::ClientMessageData::Data32([protocol, ..]) = xcb_ev.data() {
-                        if protocol == self.atoms.wm_delete_window.resource_id() {
-                            return Some(Event::Close);
-                        }
-                    }
-                }
-                None
+        }
+    }
+
+    /// Enables or disables redraw coalescing.
+    ///
+    /// When enabled, `ConfigureNotify` and `Expose` events that are already
+    /// queued together are merged into a single `Event::Redraw` holding the
+    /// window bounds at the final size. This changes the event granularity:
+    /// `Event::Resize` and `Event::Expose` are no longer emitted, and an
+    /// application only gets one `Redraw` where it would have seen several
+    /// resizes and exposes. Disabled by default.
+    /// Moves are still reported: a `ConfigureNotify` that only moves the
+    /// window is not merged, and a move along with a resize follows the
+    /// `Redraw` as `Event::Move`, or `Event::Configure` if enabled with
+    /// `set_configure_events`.
+    pub fn set_coalesce_redraws(&mut self, coalesce: bool) {
+        self.coalesce_redraws = coalesce;
+    }
+
+    /// Whether changes of the window geometry are reported as a single
+    /// `Event::Configure` rather than as `Event::Resize` and `Event::Move`.
+    /// Both models report the same changes, so an application picks the
+    /// one it prefers: once enabled, `Resize` and `Move` are no longer
+    /// emitted. Disabled by default.
+    /// Redraw coalescing takes precedence over both.
+    pub fn set_configure_events(&mut self, enable: bool) {
+        self.configure_events = enable;
+    }
+
+    /// Whether the key, button and motion events sent by other clients
+    /// with `SendEvent` are dropped rather than translated.
+    /// Such synthetic events can be injected by any client connected to
+    /// the server, so applications handling sensitive input (passwords,
+    /// confirmations) may want to reject them. Input from the XTEST
+    /// extension is not synthetic and is still accepted, but the fallback
+    /// of `send_key` and `send_button` without XTEST is rejected.
+    /// Disabled by default. The event filter still sees synthetic events,
+    /// which it can recognize with `xcb::BaseEvent::is_from_send_event`.
+    pub fn set_reject_synthetic_input(&mut self, reject: bool) {
+        self.reject_synthetic_input = reject;
+    }
+
+    /// Whether the presses of media keys (volume, playback, browser...)
+    /// are reported as `Event::MediaKey` rather than `Event::KeyPress`,
+    /// so that a media player can match them apart from the text input.
+    /// Their releases are then not reported. Disabled by default.
+    pub fn set_media_key_events(&mut self, enable: bool) {
+        self.media_key_events = enable;
+    }
+
+    /// Sets a function that sees every X event before it is translated.
+    /// Returning `false` drops the event, which then never reaches
+    /// the `Event` stream.
+    pub fn set_event_filter(&mut self, filter: Box<dyn FnMut(&xcb::Event) -> bool>) {
+        *self.event_filter.get_mut() = Some(filter);
+    }
+
+    /// Removes the function set with `set_event_filter`.
+    pub fn clear_event_filter(&mut self) {
+        *self.event_filter.get_mut() = None;
+    }
+
+    /// Returns the server time of the last key or button event received,
+    /// or `x::CURRENT_TIME` if there was none yet.
+    /// This is the timestamp to use in requests and client messages
+    /// triggered by the user, like focus changes or selection ownership.
+    pub fn last_event_time(&self) -> x::Timestamp {
+        self.last_time.get()
+    }
+
+    /// Whether `Event::KeyRelease` carries the text the key would produce,
+    /// as `Event::KeyPress` does. Disabled by default, in which case
+    /// the text of releases is empty.
+    pub fn set_release_text(&mut self, enable: bool) {
+        self.kbd.set_release_text(enable);
+    }
+
+    /// The modifiers currently held, as tracked from the key events
+    /// received by the window.
+    pub fn mods(&self) -> key::Mods {
+        self.kbd.get_mods()
+    }
+
+    /// Queries the current pointer position, in window coordinates,
+    /// and the mouse buttons held, along with the modifiers held.
+    /// Unlike the state carried by the events, this is up to date
+    /// at the time of the call.
+    /// The modifiers are those tracked from the key events, as for `mods`.
+    pub fn pointer_state(&self) -> Result<(IPoint, mouse::Buttons, key::Mods)> {
+        let conn = &self.shared.dpy.conn;
+        let reply = conn.wait_for_reply(conn.send_request(&x::QueryPointer {
+            window: self.shared.win,
+        }))?;
+        Ok((
+            event_point(reply.win_x(), reply.win_y()),
+            translate_buttons(reply.mask()),
+            self.kbd.get_mods(),
+        ))
+    }
+
+    /// Queries the physical keys currently held, whatever window
+    /// has the focus.
+    /// Keys that have no `key::Code` are not listed.
+    pub fn pressed_keys(&self) -> Result<Vec<key::Code>> {
+        let conn = &self.shared.dpy.conn;
+        let reply = conn.wait_for_reply(conn.send_request(&x::QueryKeymap {}))?;
+        Ok(self.kbd.pressed_codes(reply.keys()))
+    }
+
+    /// Returns the name of the active keyboard layout, e.g. "English (US)".
+    /// The keymap is reloaded when the keyboard or its mapping changes.
+    /// The name is empty if the server lacks the XKB extension.
+    pub fn keyboard_layout(&self) -> String {
+        self.kbd.layout_name()
+    }
+
+    /// Checks whether the connection to the X server is still usable.
+    /// Once it is not, whatever the cause (I/O error, request too long,
+    /// out of memory...), the event functions return
+    /// `Error::ConnectionClosed`.
+    pub fn is_connected(&self) -> bool {
+        self.shared.dpy.conn.has_error().is_ok()
+    }
+
+    /// Runs `f` with a single flush of the connection at the end,
+    /// instead of one flush for each change of the window made by `f`
+    /// (through this window or any of its handles).
+    /// Batches can be nested, the flush happens at the end of the outermost.
+    pub fn batch<R>(&self, f: impl FnOnce(&Window) -> R) -> Result<R> {
+        struct Batch<'a>(&'a Cell<u32>);
+        impl Drop for Batch<'_> {
+            fn drop(&mut self) {
+                self.0.set(self.0.get() - 1);
             }
-            xcb::Event::Xkb(xkb::Event::StateNotify(xcb_ev)) => {
-                if xcb_ev.device_id() as i32 == self.kbd.get_device_id() {
-                    self.kbd.update_state(&xcb_ev);
-                }
-                None
+        }
+
+        let depth = &self.shared.batch_depth;
+        depth.set(depth.get() + 1);
+        let res = {
+            let _batch = Batch(depth);
+            f(self)
+        };
+        self.shared.flush()?;
+        Ok(res)
+    }
+
+    /// Returns a cheap handle that can issue requests to this window
+    /// from other parts of the application.
+    pub fn handle(&self) -> WindowHandle {
+        WindowHandle {
+            shared: self.shared.clone(),
+        }
+    }
+
+    pub fn get_title(&self) -> String {
+        self.shared.title.borrow().clone()
+    }
+
+    pub fn set_title(&self, title: impl Into<String>) -> Result<()> {
+        self.shared.set_title(title.into())
+    }
+
+    pub fn resize(&self, width: u16, height: u16) -> Result<()> {
+        self.shared.resize(width, height)
+    }
+
+    /// Draws `buffer` to the window.
+    ///
+    /// `buffer` holds `size.w * size.h` pixels in row-major order, each pixel
+    /// being `0xAARRGGBB` (alpha is ignored for 24 bits visuals).
+    /// The pixels are uploaded to a cached pixmap which is then copied to the
+    /// window. Only 24 and 32 bits TrueColor visuals are supported,
+    /// `Error::Unsupported` is returned for other visuals.
+    /// `Error::InvalidArgument` is returned if a dimension of `size` is
+    /// negative or above 65535, or if `buffer` holds fewer pixels than `size`.
+    ///
+    /// The copy from the pixmap is made with graphics exposures disabled:
+    /// the pixmap always holds the whole image, so no part of the window is
+    /// left for the application to copy, and no `GraphicsExposure` or
+    /// `NoExposure` event is sent. An application copying with its own
+    /// graphics context through `connection` receives these events with
+    /// `set_event_filter`, as they are not translated.
+    pub fn present(&self, buffer: &[u32], size: ISize) -> Result<()> {
+        let mut presenter = self.presenter.borrow_mut();
+        if presenter.is_none() {
+            *presenter = Some(Presenter::new(
+                self.shared.dpy.clone(),
+                self.shared.win,
+                self.shared.screen,
+                self.shared.visual,
+            )?);
+        }
+        presenter
+            .as_mut()
+            .unwrap()
+            .present(self.shared.win, buffer, size)
+    }
+
+    /// Shows the content of `pixmap` in the window with the Present
+    /// extension, at the next vertical blank and without tearing.
+    /// `region` restricts the update to a part of the window, in window
+    /// coordinates, or `None` to update it all. `pixmap` must have the
+    /// depth of the window, and must not be drawn to until the server
+    /// is done with it.
+    ///
+    /// Returns the serial of the presentation, which is reported back by
+    /// `Event::PresentComplete` once the content is on screen.
+    /// Returns `Error::Unsupported` without the Present and XFixes
+    /// extensions; `present` is the fallback then.
+    /// Returns `Error::InvalidArgument` if `region` doesn't fit in the
+    /// 16 bits coordinates of the protocol or has a negative size.
+    pub fn present_pixmap(&self, pixmap: x::Pixmap, region: Option<IRect>) -> Result<u32> {
+        let rect = region.map(protocol_rect).transpose()?;
+        self.init_present()?;
+        let conn = &self.shared.dpy.conn;
+        let update = match rect {
+            Some(rect) => {
+                let update = conn.generate_id();
+                conn.send_request(&xfixes::CreateRegion {
+                    region: update,
+                    rectangles: &[rect],
+                });
+                update
             }
-            _ => None,
+            None => xfixes::Region::none(),
+        };
+        let serial = self.present_serial.get().wrapping_add(1);
+        self.present_serial.set(serial);
+        conn.send_request(&present::Pixmap {
+            window: self.shared.win,
+            pixmap,
+            serial,
+            valid: xfixes::Region::none(),
+            update,
+            x_off: 0,
+            y_off: 0,
+            target_crtc: randr::Crtc::none(),
+            wait_fence: sync::Fence::none(),
+            idle_fence: sync::Fence::none(),
+            options: present::Option::NONE.bits(),
+            // as soon as possible, i.e. at the next vertical blank
+            target_msc: 0,
+            divisor: 0,
+            remainder: 0,
+            notifies: &[],
+        });
+        if !update.is_none() {
+            // the request holds its own reference to the region
+            conn.send_request(&xfixes::DestroyRegion { region: update });
         }
+        self.shared.flush()?;
+        Ok(serial)
     }
 
-    fn make_mouse_event(
-        &self,
-        xcb_ev: &x::ButtonPressEvent,
-    ) -> (IPoint, mouse::Buttons, key::Mods) {
-        let pos = IPoint {
-            x: xcb_ev.event_x() as i32,
-            y: xcb_ev.event_y() as i32,
+    /// Negotiates the versions of the extensions needed by `present_pixmap`
+    /// and selects the completion events.
+    fn init_present(&self) -> Result<()> {
+        if self.present_ready.get() {
+            return Ok(());
+        }
+        let conn = &self.shared.dpy.conn;
+        let has_ext = |ext| conn.active_extensions().any(|e| e == ext);
+        if !has_ext(xcb::Extension::Present) || !has_ext(xcb::Extension::XFixes) {
+            return Err(Error::Unsupported(
+                "present_pixmap requires the Present and XFixes extensions",
+            ));
+        }
+        let present_ver = conn.send_request(&present::QueryVersion {
+            major_version: 1,
+            minor_version: 0,
+        });
+        // regions appeared in XFixes 2.0
+        let xfixes_ver = conn.send_request(&xfixes::QueryVersion {
+            client_major_version: 2,
+            client_minor_version: 0,
+        });
+        conn.wait_for_reply(present_ver)?;
+        if conn.wait_for_reply(xfixes_ver)?.major_version() < 2 {
+            return Err(Error::Unsupported("present_pixmap requires XFixes 2.0"));
+        }
+        conn.check_request(conn.send_request_checked(&present::SelectInput {
+            eid: conn.generate_id(),
+            window: self.shared.win,
+            event_mask: present::EventMask::COMPLETE_NOTIFY,
+        }))?;
+        self.present_ready.set(true);
+        Ok(())
+    }
+
+    /// Returns the pixel value of the color `rgb` (`0xRRGGBB`) in the visual
+    /// and colormap of the window, e.g. for a graphics context.
+    /// With a TrueColor visual, the pixel is computed from the color masks
+    /// of the visual. Otherwise, the color is allocated in the colormap of
+    /// the window, which gives the closest color available: the default
+    /// colormap of the screen, or the colormap of the parent for a child
+    /// window.
+    /// The pixel values are cached, so each color is only allocated once.
+    pub fn alloc_color(&self, rgb: u32) -> Result<u32> {
+        if let Some(&pixel) = self.colors.borrow().get(&rgb) {
+            return Ok(pixel);
+        }
+        let conn = &self.shared.dpy.conn;
+        let setup = conn.get_setup();
+        let visual = setup
+            .roots()
+            .nth(self.shared.screen)
+            .and_then(|screen| {
+                screen
+                    .allowed_depths()
+                    .flat_map(|d| d.visuals().iter())
+                    .find(|v| v.visual_id() == self.shared.visual)
+                    .copied()
+            })
+            .ok_or(Error::Unsupported("window visual not found"))?;
+
+        let pixel = if visual.class() == x::VisualClass::TrueColor {
+            true_color_pixel(
+                rgb,
+                visual.red_mask(),
+                visual.green_mask(),
+                visual.blue_mask(),
+            )
+        } else {
+            // the 8 bits components are scaled to 16 bits
+            let reply = conn.wait_for_reply(conn.send_request(&x::AllocColor {
+                cmap: self.shared.colormap,
+                red: ((rgb >> 16) & 0xff) as u16 * 0x101,
+                green: ((rgb >> 8) & 0xff) as u16 * 0x101,
+                blue: (rgb & 0xff) as u16 * 0x101,
+            }))?;
+            reply.pixel()
         };
+        self.colors.borrow_mut().insert(rgb, pixel);
+        Ok(pixel)
+    }
 
-        (pos, translate_buttons(xcb_ev.state()), self.kbd.get_mods())
+    /// Interns the atom named `name`.
+    ///
+    /// If `only_if_exists` is `true` and the atom doesn't exist,
+    /// `x::ATOM_NONE` is returned. Interned atoms are cached,
+    /// so only the first call for a name makes a round trip to the server.
+    pub fn intern_atom(&self, name: &str, only_if_exists: bool) -> Result<x::Atom> {
+        if let Some(atom) = self.shared.atom_cache.borrow().get(name) {
+            return Ok(*atom);
+        }
+        let conn = &self.shared.dpy.conn;
+        let reply = conn.wait_for_reply(conn.send_request(&x::InternAtom {
+            only_if_exists,
+            name: name.as_bytes(),
+        }))?;
+        let atom = reply.atom();
+        if !atom.is_none() {
+            self.shared
+                .atom_cache
+                .borrow_mut()
+                .insert(name.to_string(), atom);
+        }
+        Ok(atom)
     }
 
-    fn make_enterleave_point(xcb_ev: &x::EnterNotifyEvent) -> IPoint {
-        IPoint::new(xcb_ev.event_x() as i32, xcb_ev.event_y() as i32)
+    /// Returns the name of `atom`.
+    pub fn get_atom_name(&self, atom: x::Atom) -> Result<String> {
+        let conn = &self.shared.dpy.conn;
+        let reply = conn.wait_for_reply(conn.send_request(&x::GetAtomName { atom }))?;
+        Ok(reply.name().to_utf8().into_owned())
     }
-}
 
-fn translate_buttons(xcb_state: x::KeyButMask) -> mouse::Buttons {
-    let mut but = mouse::Buttons::empty();
-    if xcb_state.contains(x::KeyButMask::BUTTON1) {
-        but |= mouse::Buttons::LEFT;
+    /// Reads a property of format 32 on this window.
+    /// Returns an empty vector if the property doesn't exist.
+    /// `ty` can be `x::ATOM_ANY` to accept any type.
+    pub fn get_property_u32(&self, prop: x::Atom, ty: x::Atom) -> Result<Vec<u32>> {
+        self.get_property(prop, ty)
     }
-    if xcb_state.contains(x::KeyButMask::BUTTON2) {
-        but |= mouse::Buttons::MIDDLE;
+
+    /// Reads a property of format 8 on this window as a string.
+    /// Invalid UTF-8 sequences are replaced.
+    /// Returns an empty string if the property doesn't exist.
+    pub fn get_property_string(&self, prop: x::Atom, ty: x::Atom) -> Result<String> {
+        let bytes: Vec<u8> = self.get_property(prop, ty)?;
+        Ok(String::from_utf8_lossy(&bytes).into_owned())
     }
-    if xcb_state.contains(x::KeyButMask::BUTTON3) {
-        but |= mouse::Buttons::RIGHT;
+
+    /// Replaces a property of format 32 on this window.
+    pub fn set_property_u32(&self, prop: x::Atom, ty: x::Atom, data: &[u32]) -> Result<()> {
+        self.set_property(prop, ty, data)
+    }
+
+    /// Replaces a property of format 8 on this window.
+    pub fn set_property_string(&self, prop: x::Atom, ty: x::Atom, value: &str) -> Result<()> {
+        self.set_property(prop, ty, value.as_bytes())
+    }
+
+    /// Sets the `WM_CLASS` property (see `WindowBuilder::class`).
+    pub fn set_class(&self, instance: &str, class: &str) -> Result<()> {
+        send_class(&self.shared.dpy.conn, self.shared.win, instance, class);
+        self.shared.flush()
+    }
+
+    /// Shows or hides the window manager decorations
+    /// (see `WindowBuilder::decorated`).
+    pub fn set_decorated(&self, decorated: bool) -> Result<()> {
+        let hints = self.shared.atoms.motif_wm_hints;
+        self.set_property(hints, hints, &motif_wm_hints(decorated))
+    }
+
+    /// Whether the user can resize the window. When `false`, the window
+    /// is pinned to its current size through equal minimum and maximum
+    /// sizes in `WM_NORMAL_HINTS`; when `true`, the size constraints are
+    /// cleared.
+    pub fn set_resizable(&self, resizable: bool) -> Result<()> {
+        let fixed_size = if resizable {
+            None
+        } else {
+            Some(self.size.get())
+        };
+        self.set_property(
+            x::ATOM_WM_NORMAL_HINTS,
+            x::ATOM_WM_SIZE_HINTS,
+            &size_hints(fixed_size),
+        )
+    }
+
+    /// Sets or clears the urgency of the window, used to ask the user's attention.
+    /// Window managers typically flash the taskbar entry of urgent windows.
+    /// Both the ICCCM `UrgencyHint` of `WM_HINTS` and the EWMH
+    /// `_NET_WM_STATE_DEMANDS_ATTENTION` state are updated.
+    pub fn set_urgent(&self, urgent: bool) -> Result<()> {
+        self.update_wm_hints(|hints| {
+            if urgent {
+                hints[0] |= WM_HINTS_URGENCY;
+            } else {
+                hints[0] &= !WM_HINTS_URGENCY;
+            }
+        })?;
+        self.change_net_wm_state(
+            urgent,
+            self.shared.atoms.net_wm_state_demands_attention,
+            x::ATOM_NONE,
+        )
+    }
+
+    /// Keeps the window above other windows.
+    /// Setting it clears `set_always_below`.
+    pub fn set_always_on_top(&self, on: bool) -> Result<()> {
+        let atoms = &self.shared.atoms;
+        if on {
+            self.change_net_wm_state(false, atoms.net_wm_state_below, x::ATOM_NONE)?;
+        }
+        self.change_net_wm_state(on, atoms.net_wm_state_above, x::ATOM_NONE)
+    }
+
+    /// Keeps the window below other windows.
+    /// Setting it clears `set_always_on_top`.
+    pub fn set_always_below(&self, on: bool) -> Result<()> {
+        let atoms = &self.shared.atoms;
+        if on {
+            self.change_net_wm_state(false, atoms.net_wm_state_above, x::ATOM_NONE)?;
+        }
+        self.change_net_wm_state(on, atoms.net_wm_state_below, x::ATOM_NONE)
+    }
+
+    /// Asks the window manager to not show the window in the taskbar.
+    pub fn set_skip_taskbar(&self, skip: bool) -> Result<()> {
+        self.change_net_wm_state(
+            skip,
+            self.shared.atoms.net_wm_state_skip_taskbar,
+            x::ATOM_NONE,
+        )
+    }
+
+    /// Asks the window manager to not show the window in the pager.
+    pub fn set_skip_pager(&self, skip: bool) -> Result<()> {
+        self.change_net_wm_state(
+            skip,
+            self.shared.atoms.net_wm_state_skip_pager,
+            x::ATOM_NONE,
+        )
+    }
+
+    /// Sets the window opacity, from 0.0 (transparent) to 1.0 (opaque).
+    /// Out of range values are clamped.
+    /// This only has an effect when a compositing manager is running.
+    pub fn set_opacity(&self, opacity: f32) -> Result<()> {
+        self.set_property(
+            self.shared.atoms.net_wm_window_opacity,
+            x::ATOM_CARDINAL,
+            &[opacity_value(opacity)],
+        )
+    }
+
+    /// Takes ownership of the `PRIMARY` selection (middle-click paste)
+    /// and serves `text` to the other clients until another one takes it.
+    pub fn set_primary_text(&self, text: &str) -> Result<()> {
+        self.set_selection_text(x::ATOM_PRIMARY, text)
+    }
+
+    /// Returns the content of the `PRIMARY` selection,
+    /// or `None` if there is no owner or it cannot be converted to text.
+    /// Events received while waiting for the owner are kept for
+    /// the next calls to `wait_event` and `poll_event`.
+    /// Incremental transfers of large selections are not supported.
+    pub fn primary_text(&self) -> Result<Option<String>> {
+        self.selection_text(x::ATOM_PRIMARY)
+    }
+
+    /// Asks the window manager to move the window interactively
+    /// while the left button is held, e.g. from a client side title bar.
+    /// `anchor` is the pointer position in window coordinates.
+    pub fn begin_move(&self, anchor: IPoint) -> Result<()> {
+        self.begin_moveresize(NET_WM_MOVERESIZE_MOVE, anchor)
+    }
+
+    /// Asks the window manager to resize the window interactively from `edge`
+    /// while the left button is held.
+    /// `anchor` is the pointer position in window coordinates.
+    pub fn begin_resize(&self, edge: ResizeEdge, anchor: IPoint) -> Result<()> {
+        self.begin_moveresize(edge as u32, anchor)
+    }
+
+    fn begin_moveresize(&self, direction: u32, anchor: IPoint) -> Result<()> {
+        let conn = &self.shared.dpy.conn;
+        let pos = conn.wait_for_reply(conn.send_request(&x::TranslateCoordinates {
+            src_window: self.shared.win,
+            dst_window: self.shared.root,
+            src_x: anchor.x as i16,
+            src_y: anchor.y as i16,
+        }))?;
+        // the implicit grab of the button press must be released
+        // for the window manager to grab the pointer
+        conn.send_request(&x::UngrabPointer {
+            time: self.last_event_time(),
+        });
+        self.send_root_message(
+            self.shared.atoms.net_wm_moveresize,
+            [
+                pos.dst_x() as u32,
+                pos.dst_y() as u32,
+                direction,
+                // left button
+                1,
+                SOURCE_INDICATION_APPLICATION,
+            ],
+        )
+    }
+
+    /// Synthesizes a press or release of the physical key `code`.
+    ///
+    /// With the XTEST extension, the event is injected in the server as if
+    /// it came from the keyboard: it is delivered to the focused window,
+    /// which may not be this one. Without XTEST, the event is sent to this
+    /// window only, and some applications ignore such synthetic events.
+    pub fn send_key(&self, code: key::Code, press: bool) -> Result<()> {
+        let xcode = self
+            .kbd
+            .get_xcode(code)
+            .ok_or(Error::Unsupported("key code has no X keycode"))?;
+        let ty = if press { KEY_PRESS } else { KEY_RELEASE };
+        self.send_input(ty, xcode as u8)
+    }
+
+    /// Synthesizes a press or release of each of `buttons`
+    /// (see `send_key` for the delivery of the events).
+    pub fn send_button(&self, buttons: mouse::Buttons, press: bool) -> Result<()> {
+        let ty = if press { BUTTON_PRESS } else { BUTTON_RELEASE };
+        let numbers = [
+            (mouse::Buttons::LEFT, 1),
+            (mouse::Buttons::MIDDLE, 2),
+            (mouse::Buttons::RIGHT, 3),
+        ];
+        for (button, detail) in numbers.iter() {
+            if buttons.contains(*button) {
+                self.send_input(ty, *detail)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn send_input(&self, ty: u8, detail: u8) -> Result<()> {
+        let conn = &self.shared.dpy.conn;
+        if conn
+            .active_extensions()
+            .any(|ext| ext == xcb::Extension::Test)
+        {
+            conn.send_request(&xtest::FakeInput {
+                r#type: ty,
+                detail,
+                time: x::CURRENT_TIME,
+                root: self.shared.root,
+                root_x: 0,
+                root_y: 0,
+                deviceid: 0,
+            });
+            conn.flush()?;
+            return Ok(());
+        }
+
+        let pointer = conn.wait_for_reply(conn.send_request(&x::QueryPointer {
+            window: self.shared.win,
+        }))?;
+        let state = x::KeyButMask::from_bits_truncate(self.kbd.get_mods().to_xcb_modmask().bits());
+        let (root_x, root_y) = (pointer.root_x(), pointer.root_y());
+        let (win_x, win_y) = (pointer.win_x(), pointer.win_y());
+        let (root, win, child) = (self.shared.root, self.shared.win, x::Window::none());
+        let time = x::CURRENT_TIME;
+        match ty {
+            KEY_PRESS | KEY_RELEASE => {
+                let mask = if ty == KEY_PRESS {
+                    x::EventMask::KEY_PRESS
+                } else {
+                    x::EventMask::KEY_RELEASE
+                };
+                let ev = x::KeyPressEvent::new(
+                    detail, time, root, win, child, root_x, root_y, win_x, win_y, state, true,
+                );
+                self.send_synthetic(&ev, ty, mask)
+            }
+            _ => {
+                let mask = if ty == BUTTON_PRESS {
+                    x::EventMask::BUTTON_PRESS
+                } else {
+                    x::EventMask::BUTTON_RELEASE
+                };
+                let ev = x::ButtonPressEvent::new(
+                    detail, time, root, win, child, root_x, root_y, win_x, win_y, state, true,
+                );
+                self.send_synthetic(&ev, ty, mask)
+            }
+        }
+    }
+
+    /// Sends `ev` to this window with the response type `ty`.
+    /// The release events are aliases of the press events in xcb,
+    /// so they are built as presses and patched here.
+    fn send_synthetic<E: BaseEvent>(&self, ev: &E, ty: u8, mask: x::EventMask) -> Result<()> {
+        unsafe { (*ev.as_raw()).response_type = ty };
+        let conn = &self.shared.dpy.conn;
+        conn.send_request(&x::SendEvent {
+            propagate: false,
+            destination: x::SendEventDest::Window(self.shared.win),
+            event_mask: mask,
+            event: ev,
+        });
+        conn.flush()?;
+        Ok(())
+    }
+
+    /// Gives the input focus to the window, bypassing the window manager.
+    /// The window must be viewable. This suits override-redirect
+    /// and transient pop-ups; for top-level windows, prefer `activate`.
+    pub fn focus(&self) -> Result<()> {
+        let conn = &self.shared.dpy.conn;
+        conn.check_request(conn.send_request_checked(&x::SetInputFocus {
+            revert_to: x::InputFocus::Parent,
+            focus: self.shared.win,
+            time: self.last_event_time(),
+        }))?;
+        Ok(())
+    }
+
+    /// Grabs the keyboard, so that all the key events are reported to
+    /// this window, whatever window has the focus, until `ungrab_keyboard`.
+    /// Returns `Error::GrabFailed` if the keyboard is grabbed by another
+    /// client or the window is not viewable.
+    ///
+    /// While the grab is held, no other application receives keyboard
+    /// input, including the shortcuts of the desktop: if the application
+    /// stops processing events without releasing the grab, the session
+    /// is locked until the application exits. Keep grabs short, e.g. for
+    /// the lifetime of a modal prompt.
+    pub fn grab_keyboard(&self) -> Result<()> {
+        let conn = &self.shared.dpy.conn;
+        let reply = conn.wait_for_reply(conn.send_request(&x::GrabKeyboard {
+            owner_events: false,
+            grab_window: self.shared.win,
+            time: self.last_event_time(),
+            pointer_mode: x::GrabMode::Async,
+            keyboard_mode: x::GrabMode::Async,
+        }))?;
+        match reply.status() {
+            x::GrabStatus::Success => Ok(()),
+            status => Err(Error::GrabFailed(status)),
+        }
+    }
+
+    /// Releases the keyboard grabbed by `grab_keyboard`.
+    pub fn ungrab_keyboard(&self) -> Result<()> {
+        self.shared.dpy.conn.send_request(&x::UngrabKeyboard {
+            time: self.last_event_time(),
+        });
+        self.shared.flush()
+    }
+
+    /// Asks the window manager to raise and focus the window.
+    /// The window manager applies its focus stealing prevention policy,
+    /// and may only mark the window as demanding attention.
+    pub fn activate(&self) -> Result<()> {
+        self.send_root_message(
+            self.shared.atoms.net_active_window,
+            [
+                SOURCE_INDICATION_APPLICATION,
+                self.last_event_time(),
+                0,
+                0,
+                0,
+            ],
+        )
+    }
+
+    /// Checks whether the window has the input focus.
+    pub fn has_focus(&self) -> Result<bool> {
+        let conn = &self.shared.dpy.conn;
+        let reply = conn.wait_for_reply(conn.send_request(&x::GetInputFocus {}))?;
+        Ok(reply.focus() == self.shared.win)
+    }
+
+    /// Restricts the area of the window that receives pointer events
+    /// to `rects`, in window coordinates. Elsewhere, events go to the windows
+    /// beneath. An empty slice makes the whole window click-through.
+    pub fn set_input_region(&self, rects: &[IRect]) -> Result<()> {
+        self.set_shape(shape::Sk::Input, rects)
+    }
+
+    /// Restricts the visible area of the window to `rects`,
+    /// in window coordinates, e.g. for a non-rectangular window.
+    pub fn set_bounding_region(&self, rects: &[IRect]) -> Result<()> {
+        self.set_shape(shape::Sk::Bounding, rects)
+    }
+
+    /// Enables or disables `Event::RawMouseMove`, which reports the
+    /// unaccelerated motion of the pointer devices through XInput 2.
+    /// Unlike `MouseMove`, the motion is not clamped to pixels nor stopped
+    /// by the edges of the screen, which suits relative input such as
+    /// mouselook. The events are reported even when the window has not
+    /// the focus.
+    pub fn set_raw_motion(&self, enable: bool) -> Result<()> {
+        let conn = &self.shared.dpy.conn;
+        if !conn
+            .active_extensions()
+            .any(|ext| ext == xcb::Extension::Input)
+        {
+            return Err(Error::Unsupported("XInput extension is not available"));
+        }
+        let ver = conn.wait_for_reply(conn.send_request(&xinput::XiQueryVersion {
+            major_version: 2,
+            minor_version: 0,
+        }))?;
+        if ver.major_version() < 2 {
+            return Err(Error::Unsupported("XInput 2 is required for raw motion"));
+        }
+
+        // raw events are only delivered to the root window
+        let mask = if enable {
+            xinput::XiEventMask::RAW_MOTION
+        } else {
+            xinput::XiEventMask::empty()
+        };
+        conn.check_request(conn.send_request_checked(&xinput::XiSelectEvents {
+            window: self.shared.root,
+            masks: &[xinput::EventMaskBuf::new(
+                xinput::Device::AllMaster,
+                &[mask],
+            )],
+        }))?;
+        Ok(())
+    }
+
+    fn set_shape(&self, kind: shape::Sk, rects: &[IRect]) -> Result<()> {
+        let conn = &self.shared.dpy.conn;
+        if !conn
+            .active_extensions()
+            .any(|ext| ext == xcb::Extension::Shape)
+        {
+            return Err(Error::Unsupported("Shape extension is not available"));
+        }
+        conn.send_request(&shape::Rectangles {
+            operation: shape::So::Set,
+            destination_kind: kind,
+            ordering: x::ClipOrdering::Unsorted,
+            destination_window: self.shared.win,
+            x_offset: 0,
+            y_offset: 0,
+            rectangles: &to_xcb_rects(rects),
+        });
+        self.shared.flush()
+    }
+
+    /// Marks this window as transient for `parent` by setting `WM_TRANSIENT_FOR`.
+    ///
+    /// This is typically used for dialogs: the window manager keeps the window
+    /// stacked above its parent and groups both under a single taskbar entry
+    /// (the transient window usually gets no entry of its own).
+    /// `parent` can be obtained from another `Window` with `Window::xcb_window`.
+    pub fn set_transient_for(&self, parent: x::Window) -> Result<()> {
+        if parent.is_none() {
+            return Err(Error::InvalidWindow);
+        }
+        self.shared.dpy.conn.send_request(&x::ChangeProperty {
+            mode: x::PropMode::Replace,
+            window: self.shared.win,
+            property: self.shared.atoms.wm_transient_for,
+            r#type: x::ATOM_WINDOW,
+            data: &[parent],
+        });
+        self.shared.flush()
+    }
+
+    /// The underlying XCB window id.
+    pub fn xcb_window(&self) -> x::Window {
+        self.shared.win
+    }
+
+    /// Index of the screen the window was created on.
+    pub fn screen(&self) -> usize {
+        self.shared.screen
+    }
+
+    /// Id of the visual the window was created with: the root visual of
+    /// its screen, or the visual of its parent for a child window.
+    /// It doesn't change during the life of the window.
+    pub fn visual_id(&self) -> x::Visualid {
+        self.shared.visual
+    }
+
+    /// Depth the window was created with (significant bits per pixel):
+    /// the depth of the root window of its screen, or of the parent for
+    /// a child window. Pixmaps drawn to the window must have this depth.
+    pub fn depth(&self) -> u8 {
+        self.shared.depth
+    }
+
+    /// Format of the images of the depth of the window, to build the
+    /// data of `PutImage` requests to the window or its pixmaps.
+    /// `None` if the server lists no pixmap format for this depth.
+    pub fn image_format(&self) -> Option<ImageFormat> {
+        self.shared.dpy.image_format(self.shared.depth)
+    }
+
+    pub fn default_screen(&self) -> usize {
+        self.shared.dpy.default_screen()
+    }
+
+    /// Lists the monitors of the screen of this window.
+    /// Requires RandR 1.5, otherwise `Error::Unsupported` is returned.
+    pub fn monitors(&self) -> Result<Vec<Monitor>> {
+        monitor::get_monitors(&self.shared.dpy.conn, self.shared.win)
+    }
+
+    /// Returns the monitor under the pointer, e.g. to open a window
+    /// where the user is looking.
+    /// `None` if the pointer is on another screen or outside all monitors.
+    /// Requires RandR 1.5, as `monitors`.
+    pub fn monitor_at_pointer(&self) -> Result<Option<Monitor>> {
+        let conn = &self.shared.dpy.conn;
+        let pointer = conn.wait_for_reply(conn.send_request(&x::QueryPointer {
+            window: self.shared.root,
+        }))?;
+        if !pointer.same_screen() {
+            return Ok(None);
+        }
+        let point = event_point(pointer.root_x(), pointer.root_y());
+        Ok(monitor::monitor_at(self.monitors()?, point))
+    }
+
+    /// The connection this window was created on.
+    pub fn display(&self) -> &Display {
+        &self.shared.dpy
+    }
+
+    /// The XCB connection of the window, to issue requests this crate
+    /// doesn't cover. Requests are buffered until the next flush, which
+    /// this crate only does for its own requests: call `flush` after
+    /// requests that don't wait for a reply.
+    pub fn connection(&self) -> &xcb::Connection {
+        &self.shared.dpy.conn
+    }
+
+    /// Sends the requests buffered on the connection to the server,
+    /// including during a `batch`.
+    pub fn flush(&self) -> Result<()> {
+        self.shared.dpy.conn.flush()?;
+        Ok(())
+    }
+
+    /// Describes the X server the window is connected to.
+    pub fn server_info(&self) -> ConnectionInfo {
+        self.shared.dpy.server_info()
+    }
+
+    /// Returns the bases of `ext` on the connection of the window, e.g. to
+    /// route the events of an extension received through `set_event_filter`.
+    /// `None` if the extension is not active, such as the optional RandR,
+    /// Shape, XTEST and XInput extensions when the server lacks them.
+    pub fn extension_bases(&self, ext: xcb::Extension) -> Option<ExtensionBases> {
+        self.shared.dpy.extension_bases(ext)
+    }
+
+    fn get_property<P: x::PropEl + Copy>(&self, prop: x::Atom, ty: x::Atom) -> Result<Vec<P>> {
+        self.get_window_property(self.shared.win, prop, ty)
+    }
+
+    fn get_window_property<P: x::PropEl + Copy>(
+        &self,
+        window: x::Window,
+        prop: x::Atom,
+        ty: x::Atom,
+    ) -> Result<Vec<P>> {
+        // length of each GetProperty request, in 32 bits units
+        const CHUNK_LEN: u32 = 16384;
+
+        let conn = &self.shared.dpy.conn;
+        let mut value = Vec::new();
+        let mut offset = 0;
+        loop {
+            let reply = conn.wait_for_reply(conn.send_request(&x::GetProperty {
+                delete: false,
+                window,
+                property: prop,
+                r#type: ty,
+                long_offset: offset,
+                long_length: CHUNK_LEN,
+            }))?;
+            if reply.r#type().is_none() {
+                // property does not exist
+                return Ok(value);
+            }
+            if (ty != x::ATOM_ANY && reply.r#type() != ty) || reply.format() != P::FORMAT {
+                return Err(Error::PropertyMismatch);
+            }
+            value.extend_from_slice(reply.value::<P>());
+            if reply.bytes_after() == 0 {
+                return Ok(value);
+            }
+            offset += CHUNK_LEN;
+        }
+    }
+
+    fn set_property<P: x::PropEl>(&self, prop: x::Atom, ty: x::Atom, data: &[P]) -> Result<()> {
+        // ChangeProperty header is 24 bytes
+        let conn = &self.shared.dpy.conn;
+        let max_bytes = (conn.get_maximum_request_length() as usize * 4 - 24) & !3;
+        let chunk_len = max_bytes / mem::size_of::<P>();
+
+        let mut mode = x::PropMode::Replace;
+        let mut chunks = data.chunks(chunk_len.max(1));
+        // always send at least one request to replace with empty data
+        let first = chunks.next().unwrap_or(&[]);
+        for chunk in iter::once(first).chain(chunks) {
+            conn.send_request(&x::ChangeProperty {
+                mode,
+                window: self.shared.win,
+                property: prop,
+                r#type: ty,
+                data: chunk,
+            });
+            mode = x::PropMode::Append;
+        }
+        self.shared.flush()
+    }
+
+    /// Reads `WM_HINTS`, lets `f` modify it, and writes it back.
+    fn update_wm_hints<F: FnOnce(&mut [u32; WM_HINTS_LEN])>(&self, f: F) -> Result<()> {
+        let mut hints = [0u32; WM_HINTS_LEN];
+        let current = self.get_property::<u32>(x::ATOM_WM_HINTS, x::ATOM_WM_HINTS)?;
+        for (h, c) in hints.iter_mut().zip(current.iter()) {
+            *h = *c;
+        }
+        f(&mut hints);
+        self.set_property(x::ATOM_WM_HINTS, x::ATOM_WM_HINTS, &hints)
+    }
+
+    /// Asks the window manager to add or remove up to two `_NET_WM_STATE` atoms.
+    fn change_net_wm_state(&self, add: bool, first: x::Atom, second: x::Atom) -> Result<()> {
+        self.send_root_message(
+            self.shared.atoms.net_wm_state,
+            net_wm_state_data(add, first, second),
+        )
+    }
+
+    /// Sends a client message about this window to the window manager.
+    fn send_root_message(&self, ty: x::Atom, data: [u32; 5]) -> Result<()> {
+        let conn = &self.shared.dpy.conn;
+        let ev =
+            x::ClientMessageEvent::new(self.shared.win, ty, x::ClientMessageData::Data32(data));
+        conn.send_request(&x::SendEvent {
+            propagate: false,
+            destination: x::SendEventDest::Window(self.shared.root),
+            event_mask: x::EventMask::SUBSTRUCTURE_NOTIFY | x::EventMask::SUBSTRUCTURE_REDIRECT,
+            event: &ev,
+        });
+        self.shared.flush()
+    }
+
+    /// Returns the `Resize` to the initial size, or the `Configure` to the
+    /// initial geometry, on the first call. The initial position is the
+    /// one the window was created at: a window manager placing the window
+    /// elsewhere reports it with a later `ConfigureNotify`.
+    /// Later calls return the move that came along with the last resize,
+    /// whether it was reported by `Resize` or by a coalesced `Redraw`.
+    fn initial_event(&self) -> Option<Event> {
+        if self.initial_resize.replace(false) && !self.coalesce_redraws {
+            if self.configure_events {
+                Some(Event::Configure(self.geometry()))
+            } else {
+                Some(Event::Resize(self.size.get()))
+            }
+        } else {
+            let size = self.size.get();
+            self.pending_move
+                .take()
+                .map(|pos| move_event(pos, size, self.configure_events))
+        }
+    }
+
+    fn process_event(&self, xcb_ev: xcb::Event) -> Result<Option<Event>> {
+        if !self.filter_event(&xcb_ev) {
+            return Ok(None);
+        }
+        if self.coalesce_redraws && is_redraw_event(&xcb_ev, self.size.get()) {
+            return self.coalesce_redraw(xcb_ev).map(Some);
+        }
+        Ok(self.translate_event(xcb_ev))
+    }
+
+    /// Returns whether the event filter keeps `xcb_ev`.
+    fn filter_event(&self, xcb_ev: &xcb::Event) -> bool {
+        match self.event_filter.borrow_mut().as_mut() {
+            Some(filter) => filter(xcb_ev),
+            None => true,
+        }
+    }
+
+    /// Blocks until the connection is readable or `timeout` elapsed.
+    /// Returns whether the connection is readable.
+    fn wait_readable(&self, timeout: Duration) -> Result<bool> {
+        let mut pfd = libc::pollfd {
+            fd: self.shared.dpy.conn.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        // round up to not spin on sub-millisecond remainders
+        let timeout_ms = timeout.as_micros().div_ceil(1000);
+        let timeout_ms = timeout_ms.min(libc::c_int::MAX as u128) as libc::c_int;
+        let res = unsafe { libc::poll(&mut pfd, 1, timeout_ms) };
+        if res < 0 {
+            let err = io::Error::last_os_error();
+            if err.kind() == io::ErrorKind::Interrupted {
+                // the caller will check the deadline and poll again
+                return Ok(true);
+            }
+            return Err(err.into());
+        }
+        Ok(res > 0)
+    }
+
+    fn coalesce_redraw(&self, xcb_ev: xcb::Event) -> Result<Event> {
+        let (size, configure) =
+            fold_redraw_events(xcb_ev, self.size.get(), |size| self.next_redraw_event(size))?;
+        self.size.set(size);
+        // only the latest configure gives the final position
+        if let Some(ev) = configure {
+            let moved = self
+                .configure_position(&ev)
+                .filter(|&pos| pos != self.position.get());
+            if let Some(pos) = moved {
+                self.position.set(pos);
+                self.pending_move.set(Some(pos));
+            }
+        }
+        Ok(Event::Redraw(IRect::new(0, 0, size.w, size.h)))
+    }
+
+    /// Returns the next queued event to coalesce with a window of `size`.
+    /// Stops at the first other event, which is kept for later.
+    fn next_redraw_event(&self, size: ISize) -> Result<Option<xcb::Event>> {
+        loop {
+            if !self.pending_ev.borrow().is_empty() {
+                // events from the connection are newer than the pending ones
+                return Ok(None);
+            }
+            match self.shared.dpy.conn.poll_for_queued_event()? {
+                Some(next) if is_redraw_event(&next, size) => {
+                    if self.filter_event(&next) {
+                        return Ok(Some(next));
+                    }
+                }
+                Some(next) => {
+                    self.pending_ev.borrow_mut().push_back(next);
+                    return Ok(None);
+                }
+                None => return Ok(None),
+            }
+        }
+    }
+
+    fn translate_event(&self, xcb_ev: xcb::Event) -> Option<Event> {
+        if let xcb::Event::X(xcb_ev) = &xcb_ev {
+            if self.reject_synthetic_input && is_synthetic_input(xcb_ev) {
+                return None;
+            }
+            match xcb_ev {
+                x::Event::KeyPress(ev) | x::Event::KeyRelease(ev) => self.last_time.set(ev.time()),
+                x::Event::ButtonPress(ev) | x::Event::ButtonRelease(ev) => {
+                    self.last_time.set(ev.time())
+                }
+                _ => {}
+            }
+            if let x::Event::MotionNotify(ev) = xcb_ev {
+                if ev.detail() == x::Motion::Hint {
+                    return self.query_pointer_motion();
+                }
+            }
+            if let Some(ev) = translate_input(&self.kbd, xcb_ev) {
+                return if self.media_key_events {
+                    media_key_event(ev)
+                } else {
+                    Some(ev)
+                };
+            }
+        }
+
+        match xcb_ev {
+            xcb::Event::X(x::Event::Expose(xcb_ev)) => {
+                // only the last of a contiguous series is reported
+                if xcb_ev.count() == 0 {
+                    Some(Event::Expose)
+                } else {
+                    None
+                }
+            }
+            // `present` copies from a pixmap that is always complete, so its GC
+            // doesn't request graphics exposures; these only come from a GC
+            // created by the application, which sees them with the event filter
+            xcb::Event::X(x::Event::GraphicsExposure(_))
+            | xcb::Event::X(x::Event::NoExposure(_)) => None,
+            xcb::Event::X(x::Event::ConfigureNotify(xcb_ev)) => {
+                let size = ISize::new(xcb_ev.width() as i32, xcb_ev.height() as i32);
+                let moved = self
+                    .configure_position(&xcb_ev)
+                    .filter(|&pos| pos != self.position.get());
+                if let Some(pos) = moved {
+                    self.position.set(pos);
+                }
+                if self.configure_events {
+                    let resized = size != self.size.get();
+                    self.size.set(size);
+                    if resized || moved.is_some() {
+                        Some(Event::Configure(self.geometry()))
+                    } else {
+                        None
+                    }
+                } else if size != self.size.get() {
+                    self.size.set(size);
+                    self.pending_move.set(moved);
+                    Some(Event::Resize(size))
+                } else {
+                    moved.map(Event::Move)
+                }
+            }
+            xcb::Event::X(x::Event::ClientMessage(xcb_ev)) => {
+                match wm_protocol(&xcb_ev, self.shared.atoms.wm_protocols) {
+                    Some(protocol) if protocol == self.shared.atoms.wm_delete_window => {
+                        Some(Event::Close)
+                    }
+                    Some(protocol) => {
+                        if protocol == self.shared.atoms.net_wm_ping {
+                            self.reply_ping(&xcb_ev);
+                        } else if protocol == self.shared.atoms.wm_take_focus {
+                            self.take_focus(&xcb_ev);
+                        }
+                        None
+                    }
+                    None => self.handle_xdnd(&xcb_ev),
+                }
+            }
+            xcb::Event::X(x::Event::SelectionNotify(xcb_ev))
+                if xcb_ev.selection() == self.shared.atoms.xdnd_selection =>
+            {
+                self.finish_drop(&xcb_ev)
+            }
+            xcb_ev if self.is_keymap_event(&xcb_ev) => Some(self.reload_keymap()),
+            xcb::Event::Present(present::Event::CompleteNotify(xcb_ev))
+                if xcb_ev.window() == self.shared.win
+                    && xcb_ev.kind() == present::CompleteKind::Pixmap =>
+            {
+                Some(Event::PresentComplete {
+                    serial: xcb_ev.serial(),
+                    ust: xcb_ev.ust(),
+                    msc: xcb_ev.msc(),
+                })
+            }
+            // the keys released while the window doesn't have the focus are
+            // not reported, so the modifiers are queried when it comes back
+            xcb::Event::X(x::Event::FocusOut(xcb_ev)) => {
+                if xcb_ev.detail() != x::NotifyDetail::Pointer {
+                    self.kbd.clear_mods();
+                }
+                None
+            }
+            xcb::Event::X(x::Event::FocusIn(xcb_ev)) => {
+                if xcb_ev.detail() != x::NotifyDetail::Pointer {
+                    let conn = &self.shared.dpy.conn;
+                    if let Ok(reply) = conn.wait_for_reply(conn.send_request(&x::QueryKeymap {})) {
+                        self.kbd.reset_mods(reply.keys());
+                    }
+                }
+                None
+            }
+            xcb::Event::Xkb(xkb::Event::StateNotify(xcb_ev)) => {
+                if xcb_ev.device_id() as i32 == self.kbd.get_device_id() {
+                    self.kbd.update_state(&xcb_ev);
+                }
+                None
+            }
+            xcb::Event::X(x::Event::MapNotify(xcb_ev))
+                if xcb_ev.window() == self.shared.win && !self.mapped.replace(true) =>
+            {
+                Some(Event::StateChange(self.read_initial_state()))
+            }
+            xcb::Event::X(x::Event::PropertyNotify(xcb_ev)) => {
+                if xcb_ev.atom() == self.shared.atoms.wm_state
+                    && xcb_ev.state() == x::Property::NewValue
+                {
+                    self.read_wm_state()
+                } else {
+                    None
+                }
+            }
+            xcb::Event::X(x::Event::SelectionRequest(xcb_ev)) => {
+                self.serve_selection(&xcb_ev);
+                None
+            }
+            xcb::Event::X(x::Event::SelectionClear(xcb_ev)) => {
+                self.selections.borrow_mut().remove(&xcb_ev.selection());
+                None
+            }
+            xcb::Event::RandR(randr::Event::ScreenChangeNotify(_)) => Some(Event::MonitorsChanged),
+            xcb::Event::Input(xinput::Event::RawMotion(xcb_ev)) => Some(Event::RawMouseMove(
+                raw_motion_delta(xcb_ev.valuator_mask(), xcb_ev.axisvalues_raw()),
+            )),
+            _ => None,
+        }
+    }
+
+    /// Position in root coordinates and size of the window,
+    /// as last reported by the server.
+    fn geometry(&self) -> IRect {
+        IRect::new_ps(self.position.get(), self.size.get())
+    }
+
+    /// Whether `xcb_ev` reports a change of the keymap of the keyboard.
+    fn is_keymap_event(&self, xcb_ev: &xcb::Event) -> bool {
+        match xcb_ev {
+            xcb::Event::Xkb(xkb::Event::NewKeyboardNotify(ev)) => {
+                ev.device_id() as i32 == self.kbd.get_device_id()
+            }
+            xcb::Event::Xkb(xkb::Event::MapNotify(ev)) => {
+                ev.device_id() as i32 == self.kbd.get_device_id()
+            }
+            // with XKB, the change is also reported by MapNotify
+            xcb::Event::X(x::Event::MappingNotify(ev)) => {
+                ev.request() != x::Mapping::Pointer && !self.kbd.has_xkb()
+            }
+            _ => false,
+        }
+    }
+
+    /// Reloads the keymap after a keymap event.
+    /// A change usually comes as a burst of notifications, so those that
+    /// follow in the queue are dropped to reload the keymap only once.
+    fn reload_keymap(&self) -> Event {
+        let conn = &self.shared.dpy.conn;
+        // events from the connection are newer than the pending ones
+        while self.pending_ev.borrow().is_empty() {
+            match conn.poll_for_queued_event() {
+                Ok(Some(next)) if self.is_keymap_event(&next) => {
+                    // the keymap is reloaded whether the filter keeps it or not
+                    self.filter_event(&next);
+                }
+                Ok(Some(next)) => self.pending_ev.borrow_mut().push_back(next),
+                // errors are reported by the next wait for events
+                Ok(None) | Err(_) => break,
+            }
+        }
+        self.kbd.reload_keymap(conn);
+        Event::KeymapChanged
+    }
+
+    /// Returns the position in root coordinates of the window configured
+    /// by `ev`, or `None` if it can't be queried.
+    fn configure_position(&self, ev: &x::ConfigureNotifyEvent) -> Option<IPoint> {
+        if let Some(pos) = root_position(ev, self.override_redirect) {
+            return Some(pos);
+        }
+        let conn = &self.shared.dpy.conn;
+        let reply = conn
+            .wait_for_reply(conn.send_request(&x::TranslateCoordinates {
+                src_window: self.shared.win,
+                dst_window: self.shared.root,
+                src_x: 0,
+                src_y: 0,
+            }))
+            .ok()?;
+        Some(event_point(reply.dst_x(), reply.dst_y()))
+    }
+
+    /// Reads the pointer position after a motion hint, which also tells the
+    /// server to send the next hint.
+    fn query_pointer_motion(&self) -> Option<Event> {
+        let conn = &self.shared.dpy.conn;
+        let reply = conn
+            .wait_for_reply(conn.send_request(&x::QueryPointer {
+                window: self.shared.win,
+            }))
+            .ok()?;
+        if !reply.same_screen() {
+            return None;
+        }
+        Some(Event::MouseMove(
+            event_point(reply.win_x(), reply.win_y()),
+            translate_buttons(reply.mask()),
+            self.kbd.get_mods(),
+        ))
+    }
+
+    /// Translates the ICCCM `WM_STATE` set by the window manager.
+    /// `WithdrawnState` is not reported.
+    fn read_wm_state(&self) -> Option<Event> {
+        let wm_state = self.shared.atoms.wm_state;
+        // a failure here means the connection is broken or the window is gone,
+        // both reported by the next wait for events
+        let value = self.get_property::<u32>(wm_state, wm_state).ok()?;
+        match value.first() {
+            Some(&WM_STATE_NORMAL) => Some(Event::StateChange(State::Normal)),
+            Some(&WM_STATE_ICONIC) => Some(Event::StateChange(State::Minimized)),
+            _ => None,
+        }
+    }
+
+    /// Reads the state the window manager gave to the window when mapping
+    /// it, e.g. maximized from a saved session. The state is otherwise
+    /// only reported on changes of `WM_STATE`, which some window managers
+    /// set before applying the EWMH states.
+    fn read_initial_state(&self) -> State {
+        let atoms = &self.shared.atoms;
+        if let Some(Event::StateChange(State::Minimized)) = self.read_wm_state() {
+            return State::Minimized;
+        }
+        let net_state = self
+            .get_property::<x::Atom>(atoms.net_wm_state, x::ATOM_ATOM)
+            .unwrap_or_default();
+        let has = |atom| net_state.contains(&atom);
+        if has(atoms.net_wm_state_fullscreen) {
+            State::Fullscreen
+        } else if has(atoms.net_wm_state_maximized_vert) && has(atoms.net_wm_state_maximized_horz) {
+            State::Maximized
+        } else if has(atoms.net_wm_state_hidden) {
+            State::Hidden
+        } else {
+            State::Normal
+        }
+    }
+
+    fn set_selection_text(&self, selection: x::Atom, text: &str) -> Result<()> {
+        let conn = &self.shared.dpy.conn;
+        conn.send_request(&x::SetSelectionOwner {
+            owner: self.shared.win,
+            selection,
+            time: self.last_event_time(),
+        });
+        let owner = conn.wait_for_reply(conn.send_request(&x::GetSelectionOwner { selection }))?;
+        let mut selections = self.selections.borrow_mut();
+        if owner.owner() == self.shared.win {
+            selections.insert(selection, text.to_string());
+        } else {
+            selections.remove(&selection);
+        }
+        Ok(())
+    }
+
+    fn selection_text(&self, selection: x::Atom) -> Result<Option<String>> {
+        let conn = &self.shared.dpy.conn;
+        let owner = conn.wait_for_reply(conn.send_request(&x::GetSelectionOwner { selection }))?;
+        if owner.owner().is_none() {
+            return Ok(None);
+        }
+        if owner.owner() == self.shared.win {
+            return Ok(self.selections.borrow().get(&selection).cloned());
+        }
+
+        let prop = self.shared.atoms.toy_xcb_selection;
+        conn.send_request(&x::ConvertSelection {
+            requestor: self.shared.win,
+            selection,
+            target: self.shared.atoms.utf8_string,
+            property: prop,
+            time: self.last_event_time(),
+        });
+        conn.flush()?;
+
+        let deadline = Instant::now() + SELECTION_TIMEOUT;
+        loop {
+            while let Some(xcb_ev) = conn.poll_for_event()? {
+                match xcb_ev {
+                    xcb::Event::X(x::Event::SelectionNotify(ev))
+                        if ev.requestor() == self.shared.win && ev.selection() == selection =>
+                    {
+                        if ev.property().is_none() {
+                            return Ok(None);
+                        }
+                        let text = self.get_property::<u8>(prop, x::ATOM_ANY);
+                        conn.send_request(&x::DeleteProperty {
+                            window: self.shared.win,
+                            property: prop,
+                        });
+                        conn.flush()?;
+                        return Ok(Some(String::from_utf8_lossy(&text?).into_owned()));
+                    }
+                    xcb_ev => self.pending_ev.borrow_mut().push_back(xcb_ev),
+                }
+            }
+            let now = Instant::now();
+            if now >= deadline || !self.wait_readable(deadline - now)? {
+                return Ok(None);
+            }
+        }
+    }
+
+    /// Sends the text of a selection owned by this window to a requestor.
+    fn serve_selection(&self, req: &x::SelectionRequestEvent) {
+        let conn = &self.shared.dpy.conn;
+        let atoms = &self.shared.atoms;
+        // obsolete clients don't specify the property
+        let property = if req.property().is_none() {
+            req.target()
+        } else {
+            req.property()
+        };
+
+        let served = match self.selections.borrow().get(&req.selection()) {
+            Some(_) if req.target() == atoms.targets => {
+                conn.send_request(&x::ChangeProperty {
+                    mode: x::PropMode::Replace,
+                    window: req.requestor(),
+                    property,
+                    r#type: x::ATOM_ATOM,
+                    data: &[atoms.targets, atoms.utf8_string, x::ATOM_STRING],
+                });
+                true
+            }
+            Some(text) if req.target() == atoms.utf8_string => {
+                conn.send_request(&x::ChangeProperty {
+                    mode: x::PropMode::Replace,
+                    window: req.requestor(),
+                    property,
+                    r#type: atoms.utf8_string,
+                    data: text.as_bytes(),
+                });
+                true
+            }
+            Some(text) if req.target() == x::ATOM_STRING => {
+                conn.send_request(&x::ChangeProperty {
+                    mode: x::PropMode::Replace,
+                    window: req.requestor(),
+                    property,
+                    r#type: x::ATOM_STRING,
+                    data: &to_latin1(text),
+                });
+                true
+            }
+            _ => false,
+        };
+
+        let notify = x::SelectionNotifyEvent::new(
+            req.time(),
+            req.requestor(),
+            req.selection(),
+            req.target(),
+            if served { property } else { x::ATOM_NONE },
+        );
+        conn.send_request(&x::SendEvent {
+            propagate: false,
+            destination: x::SendEventDest::Window(req.requestor()),
+            event_mask: x::EventMask::empty(),
+            event: &notify,
+        });
+        // errors are reported by the next wait for events
+        let _ = conn.flush();
+    }
+
+    /// Handles the XDND messages sent by a drag source.
+    /// The drop is reported once the data is received (see `finish_drop`).
+    fn handle_xdnd(&self, xcb_ev: &x::ClientMessageEvent) -> Option<Event> {
+        let atoms = &self.shared.atoms;
+        let data = match xcb_ev.data() {
+            x::ClientMessageData::Data32(data) => data,
+            _ => return None,
+        };
+        let ty = xcb_ev.r#type();
+
+        if ty == atoms.xdnd_enter {
+            let source = x::Window::new(data[0]);
+            let version = data[1] >> 24;
+            if version > dnd::XDND_VERSION {
+                return None;
+            }
+            // more than 3 types are listed in XdndTypeList
+            let accept = if data[1] & 1 != 0 {
+                self.get_window_property::<x::Atom>(source, atoms.xdnd_type_list, x::ATOM_ATOM)
+                    .map(|types| types.contains(&atoms.text_uri_list))
+                    .unwrap_or(false)
+            } else {
+                data[2..5].contains(&atoms.text_uri_list.resource_id())
+            };
+            self.drag.set(Some(Drag {
+                source,
+                version,
+                accept,
+            }));
+        } else if ty == atoms.xdnd_position {
+            let drag = self.drag.get().filter(|drag| drag.is_from(data[0]))?;
+            let (flags, action) = if drag.accept {
+                (1, atoms.xdnd_action_copy)
+            } else {
+                (0, x::ATOM_NONE)
+            };
+            self.send_xdnd(
+                drag.source,
+                atoms.xdnd_status,
+                [
+                    self.shared.win.resource_id(),
+                    flags,
+                    0,
+                    0,
+                    action.resource_id(),
+                ],
+            );
+        } else if ty == atoms.xdnd_leave {
+            if self.drag.get().is_some_and(|drag| drag.is_from(data[0])) {
+                self.drag.set(None);
+            }
+        } else if ty == atoms.xdnd_drop {
+            let drag = self.drag.get().filter(|drag| drag.is_from(data[0]))?;
+            if drag.accept {
+                // the timestamp of the drop is only sent since version 1
+                let time = if drag.version >= 1 {
+                    data[2]
+                } else {
+                    x::CURRENT_TIME
+                };
+                self.shared.dpy.conn.send_request(&x::ConvertSelection {
+                    requestor: self.shared.win,
+                    selection: atoms.xdnd_selection,
+                    target: atoms.text_uri_list,
+                    property: atoms.xdnd_selection,
+                    time,
+                });
+                let _ = self.shared.dpy.conn.flush();
+            } else {
+                self.drag.set(None);
+                self.send_xdnd_finished(drag, false);
+            }
+        }
+        None
+    }
+
+    /// Reads the dropped uri list and tells the source that the drop is done.
+    fn finish_drop(&self, xcb_ev: &x::SelectionNotifyEvent) -> Option<Event> {
+        let drag = self.drag.take()?;
+        let paths = if xcb_ev.property().is_none() {
+            Vec::new()
+        } else {
+            let prop = xcb_ev.property();
+            let data = self
+                .get_property::<u8>(prop, x::ATOM_ANY)
+                .unwrap_or_default();
+            self.shared.dpy.conn.send_request(&x::DeleteProperty {
+                window: self.shared.win,
+                property: prop,
+            });
+            dnd::parse_uri_list(&data)
+        };
+        self.send_xdnd_finished(drag, !paths.is_empty());
+        if paths.is_empty() {
+            None
+        } else {
+            Some(Event::DropFiles(paths))
+        }
+    }
+
+    fn send_xdnd_finished(&self, drag: Drag, accepted: bool) {
+        let atoms = &self.shared.atoms;
+        let action = if accepted {
+            atoms.xdnd_action_copy
+        } else {
+            x::ATOM_NONE
+        };
+        self.send_xdnd(
+            drag.source,
+            atoms.xdnd_finished,
+            [
+                self.shared.win.resource_id(),
+                accepted as u32,
+                action.resource_id(),
+                0,
+                0,
+            ],
+        );
+    }
+
+    fn send_xdnd(&self, source: x::Window, ty: x::Atom, data: [u32; 5]) {
+        let conn = &self.shared.dpy.conn;
+        let ev = x::ClientMessageEvent::new(source, ty, x::ClientMessageData::Data32(data));
+        conn.send_request(&x::SendEvent {
+            propagate: false,
+            destination: x::SendEventDest::Window(source),
+            event_mask: x::EventMask::empty(),
+            event: &ev,
+        });
+        // errors are reported by the next wait for events
+        let _ = conn.flush();
+    }
+
+    /// Answers a `_NET_WM_PING` by sending it back to the root window.
+    /// Answers `WM_TAKE_FOCUS` by taking the focus, with the timestamp
+    /// of the message as required by the ICCCM.
+    fn take_focus(&self, xcb_ev: &x::ClientMessageEvent) {
+        let time = match xcb_ev.data() {
+            x::ClientMessageData::Data32([_, time, ..]) => time,
+            _ => return,
+        };
+        let conn = &self.shared.dpy.conn;
+        conn.send_request(&x::SetInputFocus {
+            revert_to: x::InputFocus::Parent,
+            focus: self.shared.win,
+            time,
+        });
+        // an error here means the connection is broken,
+        // which is reported by the next wait for events
+        let _ = conn.flush();
+    }
+
+    fn reply_ping(&self, xcb_ev: &x::ClientMessageEvent) {
+        let conn = &self.shared.dpy.conn;
+        let pong = x::ClientMessageEvent::new(self.shared.root, xcb_ev.r#type(), xcb_ev.data());
+        conn.send_request(&x::SendEvent {
+            propagate: false,
+            destination: x::SendEventDest::Window(self.shared.root),
+            event_mask: x::EventMask::SUBSTRUCTURE_NOTIFY | x::EventMask::SUBSTRUCTURE_REDIRECT,
+            event: &pong,
+        });
+        // an error here means the connection is broken,
+        // which is reported by the next wait for events
+        let _ = conn.flush();
+    }
+}
+
+/// A clonable handle to a `Window`.
+///
+/// A handle can change the window (title, size...) but does not receive
+/// its events, which remains the role of the `Window`.
+/// The X connection is shared with the window and kept alive as long
+/// as a handle exists. XCB connections must not be used concurrently from
+/// several threads without synchronization, so handles are neither `Send`
+/// nor `Sync`: they are meant to be passed around within the UI thread.
+#[derive(Clone)]
+pub struct WindowHandle {
+    shared: Rc<Shared>,
+}
+
+impl WindowHandle {
+    pub fn get_title(&self) -> String {
+        self.shared.title.borrow().clone()
+    }
+
+    pub fn set_title(&self, title: impl Into<String>) -> Result<()> {
+        self.shared.set_title(title.into())
+    }
+
+    pub fn resize(&self, width: u16, height: u16) -> Result<()> {
+        self.shared.resize(width, height)
+    }
+
+    /// The underlying XCB window id.
+    pub fn xcb_window(&self) -> x::Window {
+        self.shared.win
+    }
+}
+
+impl Shared {
+    fn set_title(&self, title: String) -> Result<()> {
+        if title != *self.title.borrow() {
+            send_title(&self.dpy.conn, &self.atoms, self.win, &title);
+            self.flush()?;
+            *self.title.borrow_mut() = title;
+        }
+        Ok(())
+    }
+
+    fn resize(&self, width: u16, height: u16) -> Result<()> {
+        self.dpy.conn.send_request(&x::ConfigureWindow {
+            window: self.win,
+            value_list: &[
+                x::ConfigWindow::Width(width as u32),
+                x::ConfigWindow::Height(height as u32),
+            ],
+        });
+        self.flush()
+    }
+
+    /// Flushes the connection, unless a batch of changes is in progress.
+    fn flush(&self) -> Result<()> {
+        if self.batch_depth.get() == 0 {
+            self.dpy.conn.flush()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "raw-window-handle")]
+impl rwh::HasWindowHandle for Window {
+    fn window_handle(&self) -> std::result::Result<rwh::WindowHandle<'_>, rwh::HandleError> {
+        let win =
+            NonZeroU32::new(self.shared.win.resource_id()).ok_or(rwh::HandleError::Unavailable)?;
+        let mut handle = rwh::XcbWindowHandle::new(win);
+        handle.visual_id = NonZeroU32::new(self.shared.visual);
+        // SAFETY: the window lives as long as self
+        Ok(unsafe { rwh::WindowHandle::borrow_raw(handle.into()) })
+    }
+}
+
+#[cfg(feature = "raw-window-handle")]
+impl rwh::HasDisplayHandle for Window {
+    fn display_handle(&self) -> std::result::Result<rwh::DisplayHandle<'_>, rwh::HandleError> {
+        let conn = NonNull::new(self.shared.dpy.conn.get_raw_conn() as *mut c_void);
+        let handle = rwh::XcbDisplayHandle::new(conn, self.shared.screen as i32);
+        // SAFETY: the connection lives as long as self
+        Ok(unsafe { rwh::DisplayHandle::borrow_raw(handle.into()) })
+    }
+}
+
+/// Instance and class names derived from the executable name.
+fn default_class() -> (String, String) {
+    let instance = env::args_os()
+        .next()
+        .and_then(|arg0| {
+            Path::new(&arg0)
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+        })
+        .unwrap_or_default();
+    let mut chars = instance.chars();
+    let class = match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    };
+    (instance, class)
+}
+
+/// Encodes the `_MOTIF_WM_HINTS` structure
+/// (flags, functions, decorations, input mode, status) to only set the decorations.
+fn motif_wm_hints(decorated: bool) -> [u32; 5] {
+    let decorations = if decorated { MWM_DECOR_ALL } else { 0 };
+    [MWM_HINTS_DECORATIONS, 0, decorations, 0, 0]
+}
+
+/// Computes the pixel of `rgb` (`0xRRGGBB`) for a TrueColor visual,
+/// each component being scaled to the width of its mask.
+fn true_color_pixel(rgb: u32, red_mask: u32, green_mask: u32, blue_mask: u32) -> u32 {
+    fn component(value: u32, mask: u32) -> u32 {
+        if mask == 0 {
+            return 0;
+        }
+        let shift = mask.trailing_zeros();
+        let max = mask >> shift;
+        ((value * max + 127) / 255) << shift
+    }
+    component((rgb >> 16) & 0xff, red_mask)
+        | component((rgb >> 8) & 0xff, green_mask)
+        | component(rgb & 0xff, blue_mask)
+}
+
+/// Encodes the ICCCM `WM_SIZE_HINTS` structure, with the minimum and maximum
+/// sizes set to `fixed_size`, or without any hint.
+fn size_hints(fixed_size: Option<ISize>) -> [u32; 18] {
+    let mut hints = [0; 18];
+    if let Some(size) = fixed_size {
+        hints[0] = P_MIN_SIZE | P_MAX_SIZE;
+        hints[5] = size.w as u32;
+        hints[6] = size.h as u32;
+        hints[7] = size.w as u32;
+        hints[8] = size.h as u32;
+    }
+    hints
+}
+
+/// Sends `WM_CLASS` as two consecutive NUL-terminated strings, as per ICCCM.
+fn send_class(conn: &xcb::Connection, win: x::Window, instance: &str, class: &str) {
+    let mut data = Vec::with_capacity(instance.len() + class.len() + 2);
+    data.extend_from_slice(instance.as_bytes());
+    data.push(0);
+    data.extend_from_slice(class.as_bytes());
+    data.push(0);
+    conn.send_request(&x::ChangeProperty {
+        mode: x::PropMode::Replace,
+        window: win,
+        property: x::ATOM_WM_CLASS,
+        r#type: x::ATOM_STRING,
+        data: &data,
+    });
+}
+
+/// Data of a `_NET_WM_STATE` client message.
+fn net_wm_state_data(add: bool, first: x::Atom, second: x::Atom) -> [u32; 5] {
+    [
+        if add {
+            NET_WM_STATE_ADD
+        } else {
+            NET_WM_STATE_REMOVE
+        },
+        first.resource_id(),
+        second.resource_id(),
+        SOURCE_INDICATION_APPLICATION,
+        0,
+    ]
+}
+
+/// Encodes `text` as ISO Latin-1 for the `STRING` target,
+/// replacing the characters that cannot be represented.
+fn to_latin1(text: &str) -> Vec<u8> {
+    text.chars()
+        .map(|c| if (c as u32) < 0x100 { c as u8 } else { b'?' })
+        .collect()
+}
+
+/// Scales `opacity` to the `_NET_WM_WINDOW_OPACITY` range.
+fn opacity_value(opacity: f32) -> u32 {
+    if opacity.is_nan() {
+        return u32::MAX;
+    }
+    (opacity.clamp(0.0, 1.0) as f64 * u32::MAX as f64).round() as u32
+}
+
+fn hostname() -> Option<String> {
+    let mut buf = [0u8; 256];
+    let res = unsafe { libc::gethostname(buf.as_mut_ptr() as *mut libc::c_char, buf.len()) };
+    if res != 0 {
+        return None;
+    }
+    let len = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+    String::from_utf8(buf[..len].to_vec()).ok()
+}
+
+fn send_title(conn: &xcb::Connection, atoms: &Atoms, win: x::Window, title: &str) {
+    conn.send_request(&x::ChangeProperty {
+        mode: x::PropMode::Replace,
+        window: win,
+        property: x::ATOM_WM_NAME,
+        r#type: x::ATOM_STRING,
+        data: title.as_bytes(),
+    });
+    conn.send_request(&x::ChangeProperty {
+        mode: x::PropMode::Replace,
+        window: win,
+        property: atoms.net_wm_name,
+        r#type: atoms.utf8_string,
+        data: title.as_bytes(),
+    });
+}
+
+/// Converts to X rectangles, normalizing the negative sizes
+/// and clamping to the 16 bits coordinates.
+fn to_xcb_rects(rects: &[IRect]) -> Vec<x::Rectangle> {
+    rects
+        .iter()
+        .map(|r| {
+            let r = r.normalized();
+            x::Rectangle {
+                x: r.x.clamp(i16::MIN as i32, i16::MAX as i32) as i16,
+                y: r.y.clamp(i16::MIN as i32, i16::MAX as i32) as i16,
+                width: r.w.min(u16::MAX as i32) as u16,
+                height: r.h.min(u16::MAX as i32) as u16,
+            }
+        })
+        .collect()
+}
+
+/// Folds `first` and the events returned by `next` into the final size
+/// of the window, starting from `size`, and the latest `ConfigureNotify`.
+/// `next` is given the size reached so far.
+fn fold_redraw_events(
+    first: xcb::Event,
+    size: ISize,
+    mut next: impl FnMut(ISize) -> Result<Option<xcb::Event>>,
+) -> Result<(ISize, Option<x::ConfigureNotifyEvent>)> {
+    let mut size = size;
+    let mut configure = None;
+    let mut xcb_ev = Some(first);
+    while let Some(ev) = xcb_ev {
+        if let xcb::Event::X(x::Event::ConfigureNotify(ev)) = ev {
+            size = ISize::new(ev.width() as i32, ev.height() as i32);
+            configure = Some(ev);
+        }
+        xcb_ev = next(size)?;
+    }
+    Ok((size, configure))
+}
+
+/// The event reporting a move to `pos` that came along with a resize:
+/// `Event::Configure` with the window `size` if `configure_events`,
+/// `Event::Move` otherwise.
+fn move_event(pos: IPoint, size: ISize, configure_events: bool) -> Event {
+    if configure_events {
+        Event::Configure(IRect::new_ps(pos, size))
+    } else {
+        Event::Move(pos)
+    }
+}
+
+/// Converts `rect` to a protocol rectangle, checking that it fits.
+fn protocol_rect(rect: IRect) -> Result<x::Rectangle> {
+    let invalid = |_| Error::InvalidArgument("rectangle out of the protocol range");
+    Ok(x::Rectangle {
+        x: i16::try_from(rect.x).map_err(invalid)?,
+        y: i16::try_from(rect.y).map_err(invalid)?,
+        width: u16::try_from(rect.w).map_err(invalid)?,
+        height: u16::try_from(rect.h).map_err(invalid)?,
+    })
+}
+
+/// Whether `xcb_ev` is coalesced into `Event::Redraw`: an `Expose`, or a
+/// `ConfigureNotify` that changes the window `size`. A `ConfigureNotify`
+/// that only moves the window needs no redraw.
+fn is_redraw_event(xcb_ev: &xcb::Event, size: ISize) -> bool {
+    match xcb_ev {
+        xcb::Event::X(x::Event::ConfigureNotify(ev)) => {
+            ISize::new(ev.width() as i32, ev.height() as i32) != size
+        }
+        xcb::Event::X(x::Event::Expose(_)) => true,
+        _ => false,
+    }
+}
+
+/// Turns the presses of media keys into `Event::MediaKey` and drops
+/// their releases. Other events are returned unchanged.
+fn media_key_event(ev: Event) -> Option<Event> {
+    match ev {
+        Event::KeyPress(sym, ..) if sym.is_media() => Some(Event::MediaKey(sym)),
+        Event::KeyRelease(sym, ..) if sym.is_media() => None,
+        ev => Some(ev),
+    }
+}
+
+/// Translates the keyboard and pointer events, which need no round-trip
+/// to the server. Returns `None` for other events.
+fn translate_input(kbd: &Keyboard, xcb_ev: &x::Event) -> Option<Event> {
+    match xcb_ev {
+        x::Event::KeyPress(xcb_ev) => Some(kbd.make_key_event(xcb_ev, true)),
+        x::Event::KeyRelease(xcb_ev) => Some(kbd.make_key_event(xcb_ev, false)),
+        x::Event::ButtonPress(xcb_ev) => {
+            // the state is the one before the press
+            let button = mouse::Button::from_detail(xcb_ev.detail());
+            let state = translate_buttons(xcb_ev.state())
+                | mouse::Buttons::from_bits_truncate(button.to_mask());
+            Some(Event::MousePress {
+                pos: event_point(xcb_ev.event_x(), xcb_ev.event_y()),
+                button,
+                state,
+                mods: kbd.get_mods(),
+            })
+        }
+        x::Event::ButtonRelease(xcb_ev) => Some(Event::MouseRelease {
+            pos: event_point(xcb_ev.event_x(), xcb_ev.event_y()),
+            button: mouse::Button::from_detail(xcb_ev.detail()),
+            state: translate_buttons(xcb_ev.state()),
+            mods: kbd.get_mods(),
+        }),
+        x::Event::EnterNotify(xcb_ev) => Some(Event::Enter(event_point(
+            xcb_ev.event_x(),
+            xcb_ev.event_y(),
+        ))),
+        x::Event::LeaveNotify(xcb_ev) => Some(Event::Leave(event_point(
+            xcb_ev.event_x(),
+            xcb_ev.event_y(),
+        ))),
+        x::Event::MotionNotify(xcb_ev) => Some(Event::MouseMove(
+            event_point(xcb_ev.event_x(), xcb_ev.event_y()),
+            translate_buttons(xcb_ev.state()),
+            kbd.get_mods(),
+        )),
+        _ => None,
+    }
+}
+
+/// Whether `xcb_ev` is an input event sent by a client with `SendEvent`.
+fn is_synthetic_input(xcb_ev: &x::Event) -> bool {
+    match xcb_ev {
+        x::Event::KeyPress(ev) | x::Event::KeyRelease(ev) => ev.is_from_send_event(),
+        x::Event::ButtonPress(ev) | x::Event::ButtonRelease(ev) => ev.is_from_send_event(),
+        x::Event::MotionNotify(ev) => ev.is_from_send_event(),
+        _ => false,
+    }
+}
+
+/// The atoms of `protocols`, without duplicates.
+/// `WM_TAKE_FOCUS` is only for windows that accept the focus.
+fn wm_protocols(atoms: &Atoms, protocols: &[Protocol], accepts_input: bool) -> Vec<x::Atom> {
+    let mut atom_list = Vec::with_capacity(protocols.len());
+    for &protocol in protocols {
+        let atom = protocol.atom(atoms);
+        if (protocol != Protocol::TakeFocus || accepts_input) && !atom_list.contains(&atom) {
+            atom_list.push(atom);
+        }
+    }
+    atom_list
+}
+
+/// Returns the protocol of a `WM_PROTOCOLS` client message
+/// (e.g. `WM_DELETE_WINDOW`), or `None` for other messages.
+fn wm_protocol(xcb_ev: &x::ClientMessageEvent, wm_protocols: x::Atom) -> Option<x::Atom> {
+    if xcb_ev.r#type() != wm_protocols {
+        return None;
+    }
+    match xcb_ev.data() {
+        x::ClientMessageData::Data32([protocol, ..]) => Some(x::Atom::new(protocol)),
+        _ => None,
+    }
+}
+
+/// Extracts the motion along the X and Y axes (valuators 0 and 1)
+/// from the values of a raw event, which are given for the valuators
+/// set in `mask` only.
+fn raw_motion_delta(mask: &[u32], values: &[xinput::Fp3232]) -> FPoint {
+    let mut delta = [0f32; 2];
+    let mut values = values.iter();
+    for valuator in 0..mask.len() * 32 {
+        if mask[valuator / 32] & (1 << (valuator % 32)) == 0 {
+            continue;
+        }
+        let value = match values.next() {
+            Some(value) => value.integral as f64 + value.frac as f64 / 4_294_967_296.0,
+            None => break,
+        };
+        if valuator < delta.len() {
+            delta[valuator] = value as f32;
+        } else {
+            break;
+        }
+    }
+    FPoint::new(delta[0], delta[1])
+}
+
+fn event_point(x: i16, y: i16) -> IPoint {
+    IPoint::new(x as i32, y as i32)
+}
+
+/// Returns the position of a `ConfigureNotify` when it is in root
+/// coordinates, or `None` when it is relative to the parent.
+/// Override-redirect windows are children of the root, and the window
+/// manager sends synthetic events in root coordinates (ICCCM 4.1.5),
+/// but the real events of a reparented window are relative to its frame.
+fn root_position(ev: &x::ConfigureNotifyEvent, override_redirect: bool) -> Option<IPoint> {
+    if override_redirect || ev.is_from_send_event() {
+        Some(event_point(ev.x(), ev.y()))
+    } else {
+        None
+    }
+}
+
+fn translate_buttons(xcb_state: x::KeyButMask) -> mouse::Buttons {
+    let mut but = mouse::Buttons::empty();
+    if xcb_state.contains(x::KeyButMask::BUTTON1) {
+        but |= mouse::Buttons::LEFT;
+    }
+    if xcb_state.contains(x::KeyButMask::BUTTON2) {
+        but |= mouse::Buttons::MIDDLE;
+    }
+    if xcb_state.contains(x::KeyButMask::BUTTON3) {
+        but |= mouse::Buttons::RIGHT;
+    }
+    but
+}
+
+#[test]
+fn net_wm_state_message() {
+    let skip_taskbar = x::Atom::new(300);
+    let skip_pager = x::Atom::new(301);
+
+    assert_eq!(
+        [1, 300, 0, 1, 0],
+        net_wm_state_data(true, skip_taskbar, x::ATOM_NONE)
+    );
+    assert_eq!(
+        [0, 300, 301, 1, 0],
+        net_wm_state_data(false, skip_taskbar, skip_pager)
+    );
+}
+
+#[test]
+fn opacity_scale() {
+    assert_eq!(0, opacity_value(0.0));
+    assert_eq!(0, opacity_value(-1.0));
+    assert_eq!(u32::MAX, opacity_value(1.0));
+    assert_eq!(u32::MAX, opacity_value(2.5));
+    assert_eq!(u32::MAX, opacity_value(f32::NAN));
+    assert_eq!(0x8000_0000, opacity_value(0.5));
+}
+
+#[test]
+fn latin1_string() {
+    assert_eq!(b"caf\xe9 ?".to_vec(), to_latin1("café €"));
+}
+
+#[test]
+fn motif_hints() {
+    assert_eq!([2, 0, 0, 0, 0], motif_wm_hints(false));
+    assert_eq!([2, 0, 1, 0, 0], motif_wm_hints(true));
+}
+
+#[test]
+fn xcb_rects() {
+    let rects = to_xcb_rects(&[IRect::new(10, 20, 30, 40), IRect::new(10, 10, -5, -5)]);
+    assert_eq!(
+        (10, 20, 30, 40),
+        (rects[0].x, rects[0].y, rects[0].width, rects[0].height)
+    );
+    assert_eq!(
+        (5, 5, 5, 5),
+        (rects[1].x, rects[1].y, rects[1].width, rects[1].height)
+    );
+    assert!(to_xcb_rects(&[]).is_empty());
+}
+
+#[test]
+fn input_translation() {
+    let kbd = Keyboard::headless("us", "");
+    let win = x::Window::new(1);
+
+    let ev = x::ButtonPressEvent::new(
+        2,
+        0,
+        win,
+        win,
+        x::WINDOW_NONE,
+        110,
+        120,
+        10,
+        20,
+        x::KeyButMask::BUTTON1,
+        true,
+    );
+    assert_eq!(
+        Some(Event::MousePress {
+            pos: IPoint::new(10, 20),
+            button: mouse::Button::Middle,
+            state: mouse::Buttons::LEFT | mouse::Buttons::MIDDLE,
+            mods: key::Mods::new(0),
+        }),
+        translate_input(&kbd, &x::Event::ButtonPress(ev))
+    );
+
+    let ev = x::ButtonReleaseEvent::new(
+        8,
+        0,
+        win,
+        win,
+        x::WINDOW_NONE,
+        110,
+        120,
+        10,
+        20,
+        x::KeyButMask::BUTTON1,
+        true,
+    );
+    assert_eq!(
+        Some(Event::MouseRelease {
+            pos: IPoint::new(10, 20),
+            button: mouse::Button::Back,
+            state: mouse::Buttons::LEFT,
+            mods: key::Mods::new(0),
+        }),
+        translate_input(&kbd, &x::Event::ButtonRelease(ev))
+    );
+
+    let ev = x::MotionNotifyEvent::new(
+        x::Motion::Normal,
+        0,
+        win,
+        win,
+        x::WINDOW_NONE,
+        105,
+        107,
+        5,
+        7,
+        x::KeyButMask::BUTTON3,
+        true,
+    );
+    assert_eq!(
+        Some(Event::MouseMove(
+            IPoint::new(5, 7),
+            mouse::Buttons::RIGHT,
+            key::Mods::new(0),
+        )),
+        translate_input(&kbd, &x::Event::MotionNotify(ev))
+    );
+
+    let ev = x::ClientMessageEvent::new(win, x::ATOM_NONE, x::ClientMessageData::Data32([0; 5]));
+    assert!(translate_input(&kbd, &x::Event::ClientMessage(ev)).is_none());
+}
+
+#[test]
+fn close_request() {
+    let win = x::Window::new(1);
+    let wm_protocols = x::Atom::new(300);
+    let wm_delete_window = x::Atom::new(301);
+
+    let ev = x::ClientMessageEvent::new(
+        win,
+        wm_protocols,
+        x::ClientMessageData::Data32([wm_delete_window.resource_id(), 0, 0, 0, 0]),
+    );
+    assert_eq!(Some(wm_delete_window), wm_protocol(&ev, wm_protocols));
+
+    let ev = x::ClientMessageEvent::new(
+        win,
+        x::Atom::new(302),
+        x::ClientMessageData::Data32([wm_delete_window.resource_id(), 0, 0, 0, 0]),
+    );
+    assert_eq!(None, wm_protocol(&ev, wm_protocols));
+}
+
+#[test]
+fn raw_motion() {
+    let fp = |integral, frac| xinput::Fp3232 { integral, frac };
+
+    assert_eq!(
+        FPoint::new(1.5, -2.25),
+        raw_motion_delta(&[0b11], &[fp(1, 0x8000_0000), fp(-3, 0xC000_0000)])
+    );
+    // vertical motion only, followed by a scroll valuator
+    assert_eq!(
+        FPoint::new(0.0, 4.0),
+        raw_motion_delta(&[0b1010], &[fp(4, 0), fp(120, 0)])
+    );
+    assert_eq!(FPoint::new(0.0, 0.0), raw_motion_delta(&[], &[]));
+}
+
+#[test]
+fn fixed_size_hints() {
+    let hints = size_hints(Some(ISize::new(640, 480)));
+    assert_eq!([48, 0, 0, 0, 0, 640, 480, 640, 480], hints[..9]);
+    assert!(hints[9..].iter().all(|&v| v == 0));
+    assert_eq!([0; 18], size_hints(None));
+}
+
+#[test]
+fn true_color() {
+    assert_eq!(
+        0x12_34_56,
+        true_color_pixel(0x12_34_56, 0xff_00_00, 0xff_00, 0xff)
+    );
+    // RGB565
+    assert_eq!(
+        0xf8_00,
+        true_color_pixel(0xff_00_00, 0xf8_00, 0x07_e0, 0x1f)
+    );
+    assert_eq!(
+        0xff_ff,
+        true_color_pixel(0xff_ff_ff, 0xf8_00, 0x07_e0, 0x1f)
+    );
+    assert_eq!(
+        0x08_41,
+        true_color_pixel(0x08_08_08, 0xf8_00, 0x07_e0, 0x1f)
+    );
+}
+
+#[test]
+fn configure_position() {
+    let win = x::Window::new(0x0040_0001);
+    let ev = x::ConfigureNotifyEvent::new(win, win, x::Window::none(), 5, 7, 100, 80, 0, false);
+    // relative to the frame of a reparented window
+    assert_eq!(None, root_position(&ev, false));
+    // relative to the root for an override-redirect window
+    assert_eq!(Some(IPoint::new(5, 7)), root_position(&ev, true));
+
+    // sent by the window manager
+    unsafe { (*xcb::Raw::as_raw(&ev)).response_type |= 0x80 };
+    assert_eq!(Some(IPoint::new(5, 7)), root_position(&ev, false));
+}
+
+#[test]
+fn zero_size() {
+    // rejected before connecting
+    assert!(matches!(
+        WindowBuilder::new(0, 480).build(),
+        Err(Error::Unsupported(_))
+    ));
+    assert!(matches!(
+        Window::new(640, 0, "zero"),
+        Err(Error::Unsupported(_))
+    ));
+}
+
+#[test]
+fn synthetic_input() {
+    let win = x::Window::new(1);
+    let ev = x::KeyPressEvent::new(
+        38,
+        0,
+        win,
+        win,
+        x::WINDOW_NONE,
+        110,
+        120,
+        10,
+        20,
+        x::KeyButMask::empty(),
+        true,
+    );
+    assert!(!is_synthetic_input(&x::Event::KeyPress(ev)));
+
+    let ev = x::KeyPressEvent::new(
+        38,
+        0,
+        win,
+        win,
+        x::WINDOW_NONE,
+        110,
+        120,
+        10,
+        20,
+        x::KeyButMask::empty(),
+        true,
+    );
+    unsafe { (*xcb::Raw::as_raw(&ev)).response_type |= 0x80 };
+    assert!(is_synthetic_input(&x::Event::KeyPress(ev)));
+}
+
+#[test]
+fn media_keys() {
+    let press = |sym| Event::KeyPress(sym, key::Code::Unknown, String::new(), key::Mods::default());
+    let release =
+        |sym| Event::KeyRelease(sym, key::Code::Unknown, String::new(), key::Mods::default());
+    assert_eq!(
+        Some(Event::MediaKey(key::Sym::VolumeUp)),
+        media_key_event(press(key::Sym::VolumeUp))
+    );
+    assert_eq!(None, media_key_event(release(key::Sym::MediaPlay)));
+    assert_eq!(
+        Some(press(key::Sym::A)),
+        media_key_event(press(key::Sym::A))
+    );
+    assert_eq!(
+        Some(release(key::Sym::A)),
+        media_key_event(release(key::Sym::A))
+    );
+}
+
+#[test]
+fn redraw_events() {
+    let win = x::Window::new(1);
+    let size = ISize::new(100, 80);
+    let configure = |x, y, w, h| {
+        xcb::Event::X(x::Event::ConfigureNotify(x::ConfigureNotifyEvent::new(
+            win,
+            win,
+            x::Window::none(),
+            x,
+            y,
+            w,
+            h,
+            0,
+            false,
+        )))
+    };
+    assert!(is_redraw_event(&configure(0, 0, 120, 80), size));
+    // only moved
+    assert!(!is_redraw_event(&configure(5, 7, 100, 80), size));
+
+    let expose = xcb::Event::X(x::Event::Expose(x::ExposeEvent::new(win, 0, 0, 10, 10, 0)));
+    assert!(is_redraw_event(&expose, size));
+    let map = xcb::Event::X(x::Event::MapNotify(x::MapNotifyEvent::new(win, win, false)));
+    assert!(!is_redraw_event(&map, size));
+}
+
+#[test]
+fn coalesced_move() {
+    let win = x::Window::new(1);
+    let configure = |x, y, w, h| {
+        xcb::Event::X(x::Event::ConfigureNotify(x::ConfigureNotifyEvent::new(
+            win,
+            win,
+            x::Window::none(),
+            x,
+            y,
+            w,
+            h,
+            0,
+            false,
+        )))
+    };
+    let expose = || xcb::Event::X(x::Event::Expose(x::ExposeEvent::new(win, 0, 0, 10, 10, 0)));
+
+    // a resize and a move, then an expose: each event is folded once
+    let mut queue = VecDeque::from(vec![configure(5, 7, 140, 90), expose()]);
+    let mut calls = 0;
+    let (size, latest) = fold_redraw_events(configure(0, 0, 120, 80), ISize::new(100, 80), |_| {
+        calls += 1;
+        Ok(queue.pop_front())
+    })
+    .unwrap();
+    assert_eq!(3, calls);
+    assert_eq!(ISize::new(140, 90), size);
+    let latest = latest.unwrap();
+    assert_eq!((5, 7), (latest.x(), latest.y()));
+
+    // with redraw coalescing and configure events, the move follows the redraw
+    assert_eq!(
+        Event::Configure(IRect::new(5, 7, 140, 90)),
+        move_event(IPoint::new(5, 7), size, true)
+    );
+    assert_eq!(
+        Event::Move(IPoint::new(5, 7)),
+        move_event(IPoint::new(5, 7), size, false)
+    );
+}
+
+#[test]
+fn protocol_rects() {
+    let rect = protocol_rect(IRect::new(-5, 7, 100, 80)).unwrap();
+    assert_eq!((-5, 7, 100, 80), (rect.x, rect.y, rect.width, rect.height));
+    for rect in [
+        IRect::new(0, 0, -1, 80),
+        IRect::new(0, 0, 100, 65536),
+        IRect::new(40000, 0, 100, 80),
+    ] {
+        assert!(matches!(
+            protocol_rect(rect),
+            Err(Error::InvalidArgument(_))
+        ));
     }
-    but
 }